@@ -0,0 +1,52 @@
+/* C interface of tree-list: lists of int64_t or void * with O(log n) positional operations. */
+#ifndef TREE_LIST_H
+#define TREE_LIST_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct TreeListI64 TreeListI64;
+typedef struct TreeListPtr TreeListPtr;
+
+/* Functions returning bool return false and do nothing if an index is out of bounds. */
+
+TreeListI64 *tree_list_i64_new(void);
+/* Accepts NULL. */
+void tree_list_i64_free(TreeListI64 *list);
+size_t tree_list_i64_len(const TreeListI64 *list);
+bool tree_list_i64_get(const TreeListI64 *list, size_t index, int64_t *out);
+bool tree_list_i64_set(TreeListI64 *list, size_t index, int64_t value);
+/* Inserts before the index-th element; index may equal the length. */
+bool tree_list_i64_insert(TreeListI64 *list, size_t index, int64_t value);
+void tree_list_i64_push_back(TreeListI64 *list, int64_t value);
+/* out may be NULL. */
+bool tree_list_i64_remove(TreeListI64 *list, size_t index, int64_t *out);
+/* Moves the elements from at into a new list; returns NULL if at is greater than the length. */
+TreeListI64 *tree_list_i64_split_off(TreeListI64 *list, size_t at);
+/* Moves all the elements of other to the end of list; other stays allocated and empty. */
+void tree_list_i64_append(TreeListI64 *list, TreeListI64 *other);
+/* Calls callback for each element in order until it returns false. */
+void tree_list_i64_for_each(const TreeListI64 *list, bool (*callback)(int64_t value, void *context), void *context);
+
+TreeListPtr *tree_list_ptr_new(void);
+void tree_list_ptr_free(TreeListPtr *list);
+size_t tree_list_ptr_len(const TreeListPtr *list);
+bool tree_list_ptr_get(const TreeListPtr *list, size_t index, void **out);
+bool tree_list_ptr_set(TreeListPtr *list, size_t index, void *value);
+bool tree_list_ptr_insert(TreeListPtr *list, size_t index, void *value);
+void tree_list_ptr_push_back(TreeListPtr *list, void *value);
+bool tree_list_ptr_remove(TreeListPtr *list, size_t index, void **out);
+TreeListPtr *tree_list_ptr_split_off(TreeListPtr *list, size_t at);
+void tree_list_ptr_append(TreeListPtr *list, TreeListPtr *other);
+void tree_list_ptr_for_each(const TreeListPtr *list, bool (*callback)(void *value, void *context), void *context);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
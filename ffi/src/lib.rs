@@ -0,0 +1,37 @@
+//! Builds the C interface of `tree-list` (`tree_list::ffi`) as a shared and a static library.
+//! The declarations are in `include/tree_list.h`.
+
+pub use tree_list::ffi::*;
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::Path, process::Command};
+
+    /// Builds the static library in a separate target directory and runs `tests/test.c` linked against it.
+    #[test]
+    fn c_test() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let target = root.join("../target/ffi");
+        let status = Command::new(option_env!("CARGO").unwrap_or("cargo"))
+            .args(["build", "--lib", "--target-dir"])
+            .arg(&target)
+            .current_dir(root)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let binary = target.join("c-test");
+        let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".into()))
+            .arg(root.join("tests/test.c"))
+            .arg("-I")
+            .arg(root.join("include"))
+            .arg(target.join("debug/libtree_list_ffi.a"))
+            .args(["-lpthread", "-ldl", "-lm", "-o"])
+            .arg(&binary)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let output = Command::new(&binary).output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"ok\n");
+    }
+}
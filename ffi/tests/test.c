@@ -0,0 +1,73 @@
+#include <assert.h>
+#include <stdio.h>
+
+#include "tree_list.h"
+
+static bool sum_until_negative(int64_t value, void *context) {
+    if (value < 0) {
+        return false;
+    }
+    *(int64_t *)context += value;
+    return true;
+}
+
+static bool count_pointers(void *value, void *context) {
+    (void)value;
+    ++*(size_t *)context;
+    return true;
+}
+
+static void test_i64(void) {
+    TreeListI64 *list = tree_list_i64_new();
+    for (int64_t i = 0; i < 1000; i++) {
+        tree_list_i64_push_back(list, i);
+    }
+    assert(tree_list_i64_insert(list, 0, -1));
+    assert(!tree_list_i64_insert(list, 1002, 0));
+    assert(tree_list_i64_len(list) == 1001);
+
+    int64_t value;
+    assert(tree_list_i64_get(list, 500, &value) && value == 499);
+    assert(!tree_list_i64_get(list, 1001, &value));
+    assert(tree_list_i64_set(list, 500, 42));
+    assert(tree_list_i64_remove(list, 500, &value) && value == 42);
+    assert(tree_list_i64_remove(list, 0, NULL));
+    assert(!tree_list_i64_remove(list, 999, NULL));
+
+    TreeListI64 *tail = tree_list_i64_split_off(list, 10);
+    assert(tail != NULL && tree_list_i64_len(list) == 10 && tree_list_i64_len(tail) == 989);
+    assert(tree_list_i64_split_off(list, 11) == NULL);
+    tree_list_i64_insert(tail, 0, -5);
+    tree_list_i64_append(list, tail);
+    assert(tree_list_i64_len(tail) == 0 && tree_list_i64_len(list) == 1000);
+    tree_list_i64_free(tail);
+
+    int64_t sum = 0;
+    tree_list_i64_for_each(list, sum_until_negative, &sum);
+    assert(sum == 45);
+    tree_list_i64_free(list);
+    tree_list_i64_free(NULL);
+}
+
+static void test_ptr(void) {
+    int values[3] = {1, 2, 3};
+    TreeListPtr *list = tree_list_ptr_new();
+    for (int i = 0; i < 3; i++) {
+        assert(tree_list_ptr_insert(list, 0, &values[i]));
+    }
+    void *value;
+    assert(tree_list_ptr_get(list, 0, &value) && *(int *)value == 3);
+    assert(tree_list_ptr_set(list, 0, NULL));
+    assert(tree_list_ptr_remove(list, 0, &value) && value == NULL);
+    size_t count = 0;
+    tree_list_ptr_for_each(list, count_pointers, &count);
+    assert(count == 2);
+    tree_list_ptr_free(list);
+}
+
+int main(void) {
+    test_i64();
+    test_ptr();
+    puts("ok");
+    return 0;
+}
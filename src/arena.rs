@@ -0,0 +1,253 @@
+//! Splay trees with parent pointers, stored in an arena.
+//!
+//! `Node` has no parent links and `balance::Splay` splays from the root towards a position,
+//! so neither can answer "where is this element now" for an element found through a handle.
+//! `EulerTourForest` and `Rga` need exactly that: a vertex, an edge or an `Id` leads to a node index,
+//! and splaying that node up to the root gives its position in amortized O(log n).
+//! The nodes live in a `Vec` so that their indices stay valid while the trees are restructured.
+
+use alloc::vec::Vec;
+
+pub(crate) const NIL: usize = usize::MAX;
+
+/// Data stored in a node together with the aggregate of its subtree.
+pub(crate) trait Summarize {
+    type Summary: Clone;
+
+    fn summary(&self) -> Self::Summary;
+
+    /// Combines the summaries of two adjacent ranges; must be associative.
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+}
+
+struct ArenaNode<T: Summarize> {
+    parent: usize,
+    children: [usize; 2],
+    /// Number of nodes in the subtree.
+    len: usize,
+    sum: T::Summary,
+    data: T,
+}
+
+/// A forest of splay trees over the nodes of an arena. A tree is named by its root, or `NIL` if empty.
+pub(crate) struct SplayArena<T: Summarize> {
+    nodes: Vec<ArenaNode<T>>,
+    free: Vec<usize>,
+}
+
+impl<T: Summarize> SplayArena<T> {
+    pub fn new() -> Self {
+        Self { nodes: Vec::new(), free: Vec::new() }
+    }
+
+    /// Adds a single-node tree and returns its node, reusing a released one if any.
+    pub fn alloc(&mut self, data: T) -> usize {
+        let node = ArenaNode { parent: NIL, children: [NIL, NIL], len: 1, sum: data.summary(), data };
+        match self.free.pop() {
+            Some(x) => {
+                self.nodes[x] = node;
+                x
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Makes `x` available to `alloc`. `x` must have been split off as a single node.
+    pub fn release(&mut self, x: usize) {
+        self.free.push(x);
+    }
+
+    pub fn get(&self, x: usize) -> &T {
+        &self.nodes[x].data
+    }
+
+    /// Modifies the data of `x`, which becomes the root of its tree.
+    pub fn modify<R>(&mut self, x: usize, f: impl FnOnce(&mut T) -> R) -> R {
+        self.splay(x);
+        let result = f(&mut self.nodes[x].data);
+        self.update(x);
+        result
+    }
+
+    /// Returns the number of nodes in the tree with root `root`.
+    pub fn len(&self, root: usize) -> usize {
+        if root == NIL { 0 } else { self.nodes[root].len }
+    }
+
+    /// Returns the summary of the tree with root `root`, or `None` if it is empty.
+    pub fn sum(&self, root: usize) -> Option<&T::Summary> {
+        if root == NIL { None } else { Some(&self.nodes[root].sum) }
+    }
+
+    pub fn is_root(&self, x: usize) -> bool {
+        self.nodes[x].parent == NIL
+    }
+
+    /// Returns the position of `x` in its tree, which becomes the root.
+    pub fn position(&mut self, x: usize) -> usize {
+        self.splay(x);
+        self.len(self.nodes[x].children[0])
+    }
+
+    /// Returns the summary of the nodes before `x` in its tree, or `None` if there are none; `x` becomes the root.
+    pub fn sum_before(&mut self, x: usize) -> Option<&T::Summary> {
+        self.splay(x);
+        self.sum(self.nodes[x].children[0])
+    }
+
+    /// Returns the node at `position` in the tree with root `root`, moved to the root.
+    /// # Panics
+    /// Panics if position is out of bounds.
+    pub fn at(&mut self, root: usize, position: usize) -> usize {
+        self.descend(root, position, |_| 1, |node| node.len)
+    }
+
+    /// Returns the node containing the `index`-th unit of weight, moved to the root,
+    /// where `weight` weighs a single node and `total` reads the weight of a subtree from its root.
+    /// # Panics
+    /// Panics if index is not less than the total weight.
+    pub fn find(&mut self, root: usize, index: usize, weight: impl Fn(&T) -> usize, total: impl Fn(&T::Summary) -> usize) -> usize {
+        self.descend(root, index, weight, |node| total(&node.sum))
+    }
+
+    fn descend(&mut self, root: usize, mut index: usize, weight: impl Fn(&T) -> usize, total: impl Fn(&ArenaNode<T>) -> usize) -> usize {
+        let mut x = root;
+        loop {
+            assert!(x != NIL, "index out of bounds");
+            let left = self.nodes[x].children[0];
+            let before = if left == NIL { 0 } else { total(&self.nodes[left]) };
+            let own = weight(&self.nodes[x].data);
+            if index < before {
+                x = left;
+            } else if index < before + own {
+                break;
+            } else {
+                index -= before + own;
+                x = self.nodes[x].children[1];
+            }
+        }
+        self.splay(x);
+        x
+    }
+
+    /// Splits the tree of `x` into the part before `x` and the part starting at `x`; returns their roots.
+    pub fn split_before(&mut self, x: usize) -> (usize, usize) {
+        self.splay(x);
+        (self.detach(x, false), x)
+    }
+
+    /// Splits the tree of `x` into the part ending at `x` and the part after `x`; returns their roots.
+    pub fn split_after(&mut self, x: usize) -> (usize, usize) {
+        self.splay(x);
+        (x, self.detach(x, true))
+    }
+
+    /// Splits the tree with root `root` before `position`; returns the roots of both parts.
+    pub fn split(&mut self, root: usize, position: usize) -> (usize, usize) {
+        if position == self.len(root) {
+            return (root, NIL);
+        }
+        let x = self.at(root, position);
+        self.split_before(x)
+    }
+
+    /// Concatenates the trees with roots `a` and `b`; returns the new root.
+    pub fn merge(&mut self, a: usize, b: usize) -> usize {
+        if a == NIL {
+            return b;
+        }
+        if b == NIL {
+            return a;
+        }
+        let mut last = a;
+        while self.nodes[last].children[1] != NIL {
+            last = self.nodes[last].children[1];
+        }
+        self.splay(last);
+        self.nodes[last].children[1] = b;
+        self.nodes[b].parent = last;
+        self.update(last);
+        last
+    }
+
+    /// Returns an iterator over the data of the tree with root `root` in order.
+    pub fn iter(&self, root: usize) -> impl Iterator<Item = &T> {
+        let mut stack = Vec::new();
+        let mut x = root;
+        core::iter::from_fn(move || {
+            while x != NIL {
+                stack.push(x);
+                x = self.nodes[x].children[0];
+            }
+            let y = stack.pop()?;
+            x = self.nodes[y].children[1];
+            Some(&self.nodes[y].data)
+        })
+    }
+
+    fn update(&mut self, x: usize) {
+        let [left, right] = self.nodes[x].children;
+        let mut len = 1;
+        let mut sum = self.nodes[x].data.summary();
+        if left != NIL {
+            let left = &self.nodes[left];
+            len += left.len;
+            sum = T::combine(&left.sum, &sum);
+        }
+        if right != NIL {
+            let right = &self.nodes[right];
+            len += right.len;
+            sum = T::combine(&sum, &right.sum);
+        }
+        let node = &mut self.nodes[x];
+        node.len = len;
+        node.sum = sum;
+    }
+
+    fn detach(&mut self, x: usize, dir: bool) -> usize {
+        let child = self.nodes[x].children[dir as usize];
+        if child != NIL {
+            self.nodes[child].parent = NIL;
+            self.nodes[x].children[dir as usize] = NIL;
+            self.update(x);
+        }
+        child
+    }
+
+    /// Lifts `x` above its parent.
+    fn rotate(&mut self, x: usize) {
+        let p = self.nodes[x].parent;
+        let g = self.nodes[p].parent;
+        let dir = self.nodes[p].children[1] == x;
+        let inner = self.nodes[x].children[!dir as usize];
+        self.nodes[p].children[dir as usize] = inner;
+        if inner != NIL {
+            self.nodes[inner].parent = p;
+        }
+        self.nodes[x].children[!dir as usize] = p;
+        self.nodes[p].parent = x;
+        self.nodes[x].parent = g;
+        if g != NIL {
+            let grand_dir = self.nodes[g].children[1] == p;
+            self.nodes[g].children[grand_dir as usize] = x;
+        }
+        self.update(p);
+        self.update(x);
+    }
+
+    /// Moves `x` to the root of its tree.
+    fn splay(&mut self, x: usize) {
+        while self.nodes[x].parent != NIL {
+            let p = self.nodes[x].parent;
+            let g = self.nodes[p].parent;
+            if g != NIL {
+                let zig_zig = (self.nodes[g].children[1] == p) == (self.nodes[p].children[1] == x);
+                self.rotate(if zig_zig { p } else { x });
+            }
+            self.rotate(x);
+        }
+    }
+}
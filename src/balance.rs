@@ -0,0 +1,445 @@
+//! Balancing strategies for `Node`.
+//!
+//! `Node` keeps lengths and calls the strategy through the hooks below;
+//! the strategy decides what state is stored in each node and how `merge` and `split_at` keep the tree shallow.
+//! Each strategy also takes the type of the stored lengths, `usize` by default;
+//! `TreeList<T, Avl<u32>>` has smaller nodes and panics if it grows beyond `u32::MAX` elements.
+
+use core::marker::PhantomData;
+use alloc::{vec, vec::Vec};
+
+use crate::{monoid::Aggregate, node::{Node, PinnedNode, Size, Tree}, tree::{avl_balance, avl_heavy_side}};
+
+pub trait Balance: Sized {
+    /// Type of the length stored in each node.
+    type Size: Size;
+
+    /// Per-node state, e.g. a height or a priority.
+    type Tag;
+
+    /// Per-list state used to create tags, e.g. a random generator.
+    type State: Default + Send + Sync;
+
+    /// Returns the tag of a new single node.
+    fn tag(state: &mut Self::State) -> Self::Tag;
+
+    /// Recomputes the tag of `node` from its children. Called at the end of `Node::update`.
+    fn update<T, M: Aggregate<T>>(_node: &mut Node<T, Self, M>) {}
+
+    /// Returns the side that is too heavy to be the sibling of the other, or `None` if they may be siblings.
+    /// `Node::join_spine` descends the heavier side until this returns `None`.
+    fn heavy_side<T, M: Aggregate<T>>(_left: Option<&Node<T, Self, M>>, _right: Option<&Node<T, Self, M>>) -> Option<bool> {
+        None
+    }
+
+    /// Restores the balance of `node` after one of its children changed.
+    fn balance<T, M: Aggregate<T>>(node: PinnedNode<T, Self, M>) -> PinnedNode<T, Self, M> {
+        node
+    }
+
+    /// Called before the `index`-th element is accessed through `&mut`; may restructure the tree.
+    fn access<T, M: Aggregate<T>>(node: PinnedNode<T, Self, M>, _index: usize) -> PinnedNode<T, Self, M> {
+        node
+    }
+
+    /// Joins `left`, the single node `pivot` and `right` in this order.
+    /// The default descends the heavier side as told by `heavy_side`.
+    fn join<T, M: Aggregate<T>>(left: Tree<T, Self, M>, pivot: PinnedNode<T, Self, M>, right: Tree<T, Self, M>) -> PinnedNode<T, Self, M> {
+        Node::join_spine(left, pivot, right)
+    }
+
+    /// Concatenates two non-empty trees.
+    fn merge<T, M: Aggregate<T>>(left: PinnedNode<T, Self, M>, right: PinnedNode<T, Self, M>) -> PinnedNode<T, Self, M> {
+        left.append(right)
+    }
+
+    /// Splits a tree before the `at`-th element, where `0 < at < node.len()`.
+    fn split_at<T, M: Aggregate<T>>(node: PinnedNode<T, Self, M>, at: usize) -> (Tree<T, Self, M>, Tree<T, Self, M>) {
+        node.split_at_recursive(at)
+    }
+
+    /// Builds a tree from single nodes in O(nodes.len()).
+    fn build<T, M: Aggregate<T>>(nodes: Vec<PinnedNode<T, Self, M>>) -> Tree<T, Self, M> {
+        Node::build_balanced(nodes)
+    }
+}
+
+/// Height-balanced tree. This is the default strategy.
+pub struct Avl<S = usize>(PhantomData<S>);
+
+impl<S: Size> Avl<S> {
+    fn height<T, M: Aggregate<T>>(node: Option<&Node<T, Self, M>>) -> u8 {
+        node.map(|node| *node.tag()).unwrap_or(0)
+    }
+}
+
+impl<S: Size> Balance for Avl<S> {
+    type Size = S;
+    /// Height of the subtree, which is at most about 1.44 log2(len).
+    type Tag = u8;
+    type State = ();
+
+    fn tag(_: &mut ()) -> u8 {
+        1
+    }
+
+    fn update<T, M: Aggregate<T>>(node: &mut Node<T, Self, M>) {
+        *node.tag_mut() = Self::height(node.child(false)).max(Self::height(node.child(true))) + 1;
+    }
+
+    fn heavy_side<T, M: Aggregate<T>>(left: Option<&Node<T, Self, M>>, right: Option<&Node<T, Self, M>>) -> Option<bool> {
+        avl_heavy_side(left, right, Self::height)
+    }
+
+    fn balance<T, M: Aggregate<T>>(node: PinnedNode<T, Self, M>) -> PinnedNode<T, Self, M> {
+        avl_balance(node, Self::height)
+    }
+}
+
+/// Randomized treap. Each list draws priorities from its own generator, which starts from a fixed seed,
+/// so performing the same operations on a list builds the same tree regardless of other lists and threads.
+pub struct Treap<S = usize>(PhantomData<S>);
+
+/// The splitmix64 generator that a list with `Treap` draws priorities from.
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl Default for SplitMix64 {
+    fn default() -> Self {
+        Self::new(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+impl<S: Size> Balance for Treap<S> {
+    type Size = S;
+    /// Priority of the node; a parent has a priority not less than its children.
+    type Tag = u64;
+    type State = SplitMix64;
+
+    fn tag(state: &mut SplitMix64) -> u64 {
+        state.next_u64()
+    }
+
+    fn merge<T, M: Aggregate<T>>(mut left: PinnedNode<T, Self, M>, mut right: PinnedNode<T, Self, M>) -> PinnedNode<T, Self, M> {
+        if left.tag() >= right.tag() {
+            let child = Node::merge(left.replace_child(true, None), Some(right));
+            left.replace_child(true, child);
+            unsafe { left.as_mut().get_unchecked_mut() }.update();
+            left
+        } else {
+            let child = Node::merge(Some(left), right.replace_child(false, None));
+            right.replace_child(false, child);
+            unsafe { right.as_mut().get_unchecked_mut() }.update();
+            right
+        }
+    }
+
+    /// Merges the pieces, which keeps the priorities in heap order.
+    fn join<T, M: Aggregate<T>>(left: Tree<T, Self, M>, pivot: PinnedNode<T, Self, M>, right: Tree<T, Self, M>) -> PinnedNode<T, Self, M> {
+        Node::merge(Node::merge(left, Some(pivot)), right).unwrap()
+    }
+
+    /// Splits without rotations, which keeps the priorities in heap order.
+    fn split_at<T, M: Aggregate<T>>(mut node: PinnedNode<T, Self, M>, at: usize) -> (Tree<T, Self, M>, Tree<T, Self, M>) {
+        let left_len = node.child_len(false);
+        if at <= left_len {
+            let (left, right) = node.replace_child(false, None).unwrap().split_at(at);
+            node.replace_child(false, right);
+            unsafe { node.as_mut().get_unchecked_mut() }.update();
+            (left, Some(node))
+        } else {
+            let (left, right) = node.replace_child(true, None).unwrap().split_at(at - left_len - 1);
+            node.replace_child(true, left);
+            unsafe { node.as_mut().get_unchecked_mut() }.update();
+            (Some(node), right)
+        }
+    }
+
+    /// Builds the Cartesian tree of the priorities, keeping the right spine on a stack.
+    fn build<T, M: Aggregate<T>>(nodes: Vec<PinnedNode<T, Self, M>>) -> Tree<T, Self, M> {
+        let mut spine: Vec<PinnedNode<T, Self, M>> = vec![];
+        let close = |mut node: PinnedNode<T, Self, M>, right| {
+            node.replace_child(true, right);
+            unsafe { node.as_mut().get_unchecked_mut() }.update();
+            Some(node)
+        };
+        for mut node in nodes {
+            let mut last = None;
+            while spine.last().map(|top| top.tag() < node.tag()).unwrap_or(false) {
+                last = close(spine.pop().unwrap(), last);
+            }
+            node.replace_child(false, last);
+            spine.push(node);
+        }
+        let mut last = None;
+        while let Some(top) = spine.pop() {
+            last = close(top, last);
+        }
+        last
+    }
+}
+
+/// Weight-balanced tree: the sizes of siblings differ by at most a factor of `DELTA`.
+pub struct WeightBalanced<S = usize>(PhantomData<S>);
+
+impl<S: Size> WeightBalanced<S> {
+    const DELTA: usize = 3;
+    const GAMMA: usize = 2;
+
+    fn weight<T, M: Aggregate<T>>(node: Option<&Node<T, Self, M>>) -> usize {
+        node.map(|node| node.len()).unwrap_or(0) + 1
+    }
+
+    /// Lifts the child on the heavy side `dir`, with a double rotation if its inner grandchild is too heavy.
+    fn lift<T, M: Aggregate<T>>(mut node: PinnedNode<T, Self, M>, dir: bool) -> PinnedNode<T, Self, M> {
+        let child = node.child(dir).unwrap();
+        if Self::weight(child.child(!dir)) >= Self::GAMMA * Self::weight(child.child(dir)) {
+            let child = node.replace_child(dir, None).unwrap().rotate(dir);
+            node.replace_child(dir, Some(child));
+            unsafe { node.as_mut().get_unchecked_mut() }.update();
+        }
+        node.rotate(!dir)
+    }
+}
+
+impl<S: Size> Balance for WeightBalanced<S> {
+    type Size = S;
+    type Tag = ();
+    type State = ();
+
+    fn tag(_: &mut ()) {}
+
+    fn heavy_side<T, M: Aggregate<T>>(left: Option<&Node<T, Self, M>>, right: Option<&Node<T, Self, M>>) -> Option<bool> {
+        let lw = Self::weight(left);
+        let rw = Self::weight(right);
+        if lw * Self::DELTA < rw {
+            Some(true)
+        } else if rw * Self::DELTA < lw {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn balance<T, M: Aggregate<T>>(node: PinnedNode<T, Self, M>) -> PinnedNode<T, Self, M> {
+        match Self::heavy_side(node.child(false), node.child(true)) {
+            Some(dir) => Self::lift(node, dir),
+            None => node,
+        }
+    }
+}
+
+/// Splay tree. `merge`, `split_at` and `access` splay the node at the position to the root,
+/// which gives amortized O(log(len)) operations and fast repeated access near the same position.
+/// Access through `&self` (`get`, `Index`, `iter_from`, `leftmost`, `rightmost`) does not restructure the tree,
+/// so it costs O(depth), which is O(len) on the chain left by a run of `push_back`; the bounds above do not apply to it.
+pub struct Splay<S = usize>(PhantomData<S>);
+
+impl<S: Size> Splay<S> {
+    /// Moves the `index`-th node to the root by bottom-up splaying.
+    pub fn splay_at<T, M: Aggregate<T>>(mut node: PinnedNode<T, Self, M>, mut index: usize) -> PinnedNode<T, Self, M> {
+        let mut path = vec![];
+        loop {
+            let left_len = node.child_len(false);
+            if index == left_len {
+                break;
+            }
+            let dir = index > left_len;
+            if dir {
+                index -= left_len + 1;
+            }
+            let child = node.replace_child(dir, None).unwrap();
+            path.push((node, dir));
+            node = child;
+        }
+        while let Some((mut parent, dir)) = path.pop() {
+            parent.replace_child(dir, Some(node));
+            unsafe { parent.as_mut().get_unchecked_mut() }.update();
+            node = match path.pop() {
+                Some((mut grandparent, grand_dir)) if grand_dir == dir => {
+                    grandparent.replace_child(grand_dir, Some(parent));
+                    unsafe { grandparent.as_mut().get_unchecked_mut() }.update();
+                    grandparent.rotate(!grand_dir).rotate(!dir)
+                }
+                Some((mut grandparent, grand_dir)) => {
+                    grandparent.replace_child(grand_dir, Some(parent.rotate(!dir)));
+                    unsafe { grandparent.as_mut().get_unchecked_mut() }.update();
+                    grandparent.rotate(!grand_dir)
+                }
+                None => parent.rotate(!dir),
+            };
+        }
+        node
+    }
+}
+
+impl<S: Size> Balance for Splay<S> {
+    type Size = S;
+    type Tag = ();
+    type State = ();
+
+    fn tag(_: &mut ()) {}
+
+    fn access<T, M: Aggregate<T>>(node: PinnedNode<T, Self, M>, index: usize) -> PinnedNode<T, Self, M> {
+        Self::splay_at(node, index)
+    }
+
+    fn merge<T, M: Aggregate<T>>(left: PinnedNode<T, Self, M>, right: PinnedNode<T, Self, M>) -> PinnedNode<T, Self, M> {
+        let last = left.len() - 1;
+        let mut left = Self::splay_at(left, last);
+        left.replace_child(true, Some(right));
+        unsafe { left.as_mut().get_unchecked_mut() }.update();
+        left
+    }
+
+    fn split_at<T, M: Aggregate<T>>(node: PinnedNode<T, Self, M>, at: usize) -> (Tree<T, Self, M>, Tree<T, Self, M>) {
+        let mut right = Self::splay_at(node, at);
+        let left = right.replace_child(false, None);
+        unsafe { right.as_mut().get_unchecked_mut() }.update();
+        (left, Some(right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeList;
+    use alloc::vec::Vec;
+
+    fn depth<T, B: Balance>(node: Option<&Node<T, B>>) -> usize {
+        node.map(|node| 1 + depth(node.child(false)).max(depth(node.child(true)))).unwrap_or(0)
+    }
+
+    fn check<B: Balance>() -> usize {
+        let mut list = TreeList::<usize, B>::default();
+        let mut expected = vec![];
+        let mut seed = 1u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for i in 0 .. 2000 {
+            match next(4) {
+                0 | 1 => {
+                    let at = next(expected.len() + 1);
+                    list.insert(at, i);
+                    expected.insert(at, i);
+                }
+                2 if !expected.is_empty() => {
+                    let at = next(expected.len());
+                    assert_eq!(list.remove(at), Some(expected.remove(at)));
+                }
+                _ => {
+                    let at = next(expected.len() + 1);
+                    let mut right = list.split_off(at);
+                    assert_eq!(right.len(), expected.len() - at);
+                    list.append(&mut right);
+                }
+            }
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+        for (i, &x) in expected.iter().enumerate() {
+            assert_eq!(list[i], x);
+        }
+        list.reverse();
+        expected.reverse();
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+        depth(list.root.as_deref())
+    }
+
+    #[test]
+    fn strategies() {
+        assert!(check::<Avl>() <= 15);
+        assert!(check::<Treap>() <= 40);
+        assert!(check::<WeightBalanced>() <= 20);
+        check::<Splay>();
+    }
+
+    #[test]
+    fn narrow_sizes() {
+        assert!(check::<Avl<u32>>() <= 15);
+        assert!(check::<WeightBalanced<u16>>() <= 20);
+        check::<Splay<u16>>();
+        let mut list = (0 .. 255).collect::<TreeList<u32, Treap<u8>>>();
+        let mut right = list.split_off(100);
+        right.reverse();
+        list.append(&mut right);
+        assert_eq!(list.len(), 255);
+        assert_eq!(list[100], 254);
+    }
+
+    #[test]
+    #[should_panic(expected = "length 256 overflows the index type u8")]
+    fn push_overflow() {
+        let mut list = (0 .. 255).collect::<TreeList<u32, Avl<u8>>>();
+        list.push_back(255);
+    }
+
+    #[test]
+    #[should_panic(expected = "overflows the index type u16")]
+    fn append_overflow() {
+        let mut list = (0 .. 40000).collect::<TreeList<u32, Avl<u16>>>();
+        let mut other = (0 .. 40000).collect::<TreeList<u32, Avl<u16>>>();
+        list.append(&mut other);
+    }
+
+    #[test]
+    fn treap_priorities_per_list() {
+        fn tags(node: Option<&Node<u32, Treap>>, out: &mut Vec<u64>) {
+            if let Some(node) = node {
+                out.push(*node.tag());
+                tags(node.child(false), out);
+                tags(node.child(true), out);
+            }
+        }
+        let build = || {
+            let mut list = TreeList::<u32, Treap>::default();
+            for i in 0 .. 100 {
+                list.insert(i as usize / 2, i);
+            }
+            list
+        };
+        let (first, _other, second) = (build(), build(), build());
+        let (mut a, mut b) = (vec![], vec![]);
+        tags(first.root.as_deref(), &mut a);
+        tags(second.root.as_deref(), &mut b);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn deep_splay() {
+        let mut list = crate::SplayTreeList::with_capacity(200000);
+        for i in 0 .. 200000 {
+            list.push_back(i);
+        }
+        assert_eq!(list[0], 0);
+        assert_eq!(list.pop_front(), Some(0));
+        assert_eq!(list.leftmost(|&x| x >= 100000), Some(99999));
+    }
+
+    #[test]
+    fn splay_access() {
+        let mut list = (0 .. 1000).collect::<crate::SplayTreeList<_>>();
+        for i in 0 .. 1000 {
+            assert_eq!(*list.access(i), i);
+            assert_eq!(*list.root.as_ref().unwrap().data(), i);
+        }
+        list[500] = 0;
+        assert_eq!(*list.root.as_ref().unwrap().data(), 0);
+        assert_eq!(list.get(500), Some(&0));
+        assert_eq!(list.get(1000), None);
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::Instant;
+use tree_list::{*, balance::*};
+
+const N: usize = 200_000;
+
+fn bench<B: Balance>(name: &str) {
+  let mut seed = 1u64;
+  let mut next = |n: usize| {
+    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    (seed >> 33) as usize % n
+  };
+  let mut list = TreeList::<usize, B>::default();
+  let start = Instant::now();
+  for i in 0 .. N {
+    list.push_back(i);
+  }
+  let push = start.elapsed();
+  let start = Instant::now();
+  for i in 0 .. N {
+    list.insert(next(list.len() + 1), i);
+  }
+  let insert = start.elapsed();
+  let start = Instant::now();
+  let mut sum = 0;
+  for _ in 0 .. N {
+    sum += list[next(list.len())];
+  }
+  let index = start.elapsed();
+  let start = Instant::now();
+  for _ in 0 .. N {
+    let mut right = list.split_off(next(list.len() + 1));
+    list.append(&mut right);
+  }
+  let split = start.elapsed();
+  let start = Instant::now();
+  for _ in 0 .. N {
+    list.remove(next(list.len()));
+  }
+  let remove = start.elapsed();
+  println!("{:<15} push_back {:>10.2?} insert {:>10.2?} index {:>10.2?} split+append {:>10.2?} remove {:>10.2?} ({})", name, push, insert, index, split, remove, sum % 10);
+}
+
+fn main() {
+  bench::<Avl>("Avl");
+  bench::<Treap>("Treap");
+  bench::<WeightBalanced>("WeightBalanced");
+  bench::<Splay>("Splay");
+}
@@ -0,0 +1,86 @@
+use std::{alloc::{GlobalAlloc, Layout, System}, sync::atomic::{AtomicUsize, Ordering}, time::Instant};
+use tree_list::*;
+
+const N: usize = 1_000_000;
+
+/// Counts the bytes currently allocated, so that memory is measured instead of estimated.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    System.dealloc(ptr, layout)
+  }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Returns the value built by `f` and the change in allocated bytes per element while running it.
+fn measure<L, F: FnOnce() -> L>(f: F) -> (L, f64) {
+  let before = ALLOCATED.load(Ordering::Relaxed);
+  let list = f();
+  let bytes = ALLOCATED.load(Ordering::Relaxed) as f64 - before as f64;
+  (list, bytes / N as f64)
+}
+
+/// Cuts the list at random positions and puts the pieces back in a different order.
+fn churn<L>(list: &mut L, len: usize, split_off: impl Fn(&mut L, usize) -> L, append: impl Fn(&mut L, &mut L)) {
+  let mut seed = 7u64;
+  for _ in 0 .. 10000 {
+    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    let mut right = split_off(list, (seed >> 33) as usize % (len + 1));
+    append(&mut right, list);
+    std::mem::swap(list, &mut right);
+  }
+}
+
+fn bench<T: Copy + Default>(name: &str) {
+  let start = Instant::now();
+  let (mut list, tree_bytes) = measure(|| (0 .. N).map(|_| T::default()).collect::<TreeList<_>>());
+  let mut sum = list.iter().count();
+  let tree = start.elapsed();
+  let start = Instant::now();
+  let (mut chunked, chunk_bytes) = measure(|| (0 .. N).map(|_| T::default()).collect::<ChunkedTreeList<_>>());
+  sum += chunked.iter().count();
+  let chunk = start.elapsed();
+  println!("{}: build+iter TreeList {:?}, ChunkedTreeList {:?}", name, tree, chunk);
+  println!("{}: bytes/element TreeList {:.2}, ChunkedTreeList {:.2}", name, tree_bytes, chunk_bytes);
+
+  let (_, delta) = measure(|| churn(&mut list, N, |list, at| list.split_off(at), |list, other| list.append(other)));
+  let tree_bytes = tree_bytes + delta;
+  let (_, delta) = measure(|| churn(&mut chunked, N, |list, at| list.split_off(at), |list, other| list.append(other)));
+  let chunk_bytes = chunk_bytes + delta;
+  println!("{}: bytes/element after split/append churn TreeList {:.2}, ChunkedTreeList {:.2}", name, tree_bytes, chunk_bytes);
+
+  let mut list = TreeList::<T>::new();
+  let mut chunked = ChunkedTreeList::<T>::new();
+  let mut seed = 1u64;
+  let start = Instant::now();
+  for i in 0 .. N / 10 {
+    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    list.insert((seed >> 33) as usize % (i + 1), T::default());
+  }
+  let tree = start.elapsed();
+  let start = Instant::now();
+  for i in 0 .. N / 10 {
+    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    chunked.insert((seed >> 33) as usize % (i + 1), T::default());
+  }
+  let chunk = start.elapsed();
+  println!("{}: random insert TreeList {:?}, ChunkedTreeList {:?}", name, tree, chunk);
+  assert_eq!(sum, 2 * N);
+}
+
+fn main() {
+  bench::<u8>("u8");
+  bench::<u32>("u32");
+  bench::<u64>("u64");
+}
@@ -0,0 +1,196 @@
+//! Compares `TreeList` with `Vec`, `VecDeque` and `BTreeMap`.
+//!
+//! Usage: `cargo run --release --bin bench-compare -- [--markdown] [max exponent, default 7]`
+//!
+//! Prints nanoseconds per operation as CSV (`size,operation,structure,ns`) or as a markdown table per size.
+//! Repeated operations stop after `BUDGET`, so the O(n) operations of `Vec` stay tractable at 10^7.
+//! `BTreeMap` has no positional access, so its column is a key lookup, not an order statistic: position `i` of the
+//! initial list is key `i << 32`, and positional operations look up the first key not less than it. Inserted keys do not
+//! shift the later ones, so its times are not those of a rank/select structure.
+
+use std::{collections::{BTreeMap, VecDeque}, hint::black_box, time::{Duration, Instant}};
+use tree_list::TreeList;
+
+const BUDGET: Duration = Duration::from_millis(300);
+const MAX_OPS: usize = 100_000;
+
+struct Rng(u64);
+
+impl Rng {
+  fn below(&mut self, n: usize) -> usize {
+    self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    ((self.0 >> 33) as usize) % n
+  }
+}
+
+trait Subject: Sized {
+  const NAME: &'static str;
+  fn new() -> Self;
+  fn build(n: usize) -> Self;
+  fn len(&self) -> usize;
+  fn push_back(&mut self, x: u64);
+  fn insert(&mut self, at: usize, x: u64);
+  fn remove(&mut self, at: usize);
+  fn get(&self, at: usize) -> u64;
+  fn split_append(&mut self, at: usize);
+  fn sum(&self) -> u64;
+  fn insert_sorted(&mut self, x: u64);
+}
+
+impl Subject for TreeList<u64> {
+  const NAME: &'static str = "TreeList";
+  fn new() -> Self { TreeList::new() }
+  fn build(n: usize) -> Self { (0 .. n as u64).collect() }
+  fn len(&self) -> usize { TreeList::len(self) }
+  fn push_back(&mut self, x: u64) { TreeList::push_back(self, x) }
+  fn insert(&mut self, at: usize, x: u64) { TreeList::insert(self, at, x) }
+  fn remove(&mut self, at: usize) { TreeList::remove(self, at); }
+  fn get(&self, at: usize) -> u64 { self[at] }
+  fn split_append(&mut self, at: usize) {
+    let mut right = self.split_off(at);
+    self.append(&mut right);
+  }
+  fn sum(&self) -> u64 { self.iter().sum() }
+  fn insert_sorted(&mut self, x: u64) { TreeList::insert_sorted(self, x) }
+}
+
+impl Subject for Vec<u64> {
+  const NAME: &'static str = "Vec";
+  fn new() -> Self { Vec::new() }
+  fn build(n: usize) -> Self { (0 .. n as u64).collect() }
+  fn len(&self) -> usize { Vec::len(self) }
+  fn push_back(&mut self, x: u64) { self.push(x) }
+  fn insert(&mut self, at: usize, x: u64) { Vec::insert(self, at, x) }
+  fn remove(&mut self, at: usize) { Vec::remove(self, at); }
+  fn get(&self, at: usize) -> u64 { self[at] }
+  fn split_append(&mut self, at: usize) {
+    let mut right = self.split_off(at);
+    self.append(&mut right);
+  }
+  fn sum(&self) -> u64 { self.iter().sum() }
+  fn insert_sorted(&mut self, x: u64) {
+    let at = self.partition_point(|&y| y < x);
+    Vec::insert(self, at, x)
+  }
+}
+
+impl Subject for VecDeque<u64> {
+  const NAME: &'static str = "VecDeque";
+  fn new() -> Self { VecDeque::new() }
+  fn build(n: usize) -> Self { (0 .. n as u64).collect() }
+  fn len(&self) -> usize { VecDeque::len(self) }
+  fn push_back(&mut self, x: u64) { VecDeque::push_back(self, x) }
+  fn insert(&mut self, at: usize, x: u64) { VecDeque::insert(self, at, x) }
+  fn remove(&mut self, at: usize) { VecDeque::remove(self, at); }
+  fn get(&self, at: usize) -> u64 { self[at] }
+  fn split_append(&mut self, at: usize) {
+    let mut right = self.split_off(at);
+    self.append(&mut right);
+  }
+  fn sum(&self) -> u64 { self.iter().sum() }
+  fn insert_sorted(&mut self, x: u64) {
+    let at = self.partition_point(|&y| y < x);
+    VecDeque::insert(self, at, x)
+  }
+}
+
+/// Keys are `position << 32 | tiebreak`; values are the elements. Positions are looked up as keys, without rank/select.
+impl Subject for BTreeMap<u64, u64> {
+  const NAME: &'static str = "BTreeMap (key lookup)";
+  fn new() -> Self { BTreeMap::new() }
+  fn build(n: usize) -> Self { (0 .. n as u64).map(|x| (x << 32, x)).collect() }
+  fn len(&self) -> usize { BTreeMap::len(self) }
+  fn push_back(&mut self, x: u64) { BTreeMap::insert(self, (self.len() as u64) << 32, x); }
+  fn insert(&mut self, at: usize, x: u64) { BTreeMap::insert(self, (at as u64) << 32 | (x & 0xffff_ffff), x); }
+  fn remove(&mut self, at: usize) {
+    let key = self.range((at as u64) << 32 ..).next().or_else(|| self.iter().next_back()).map(|(&key, _)| key);
+    BTreeMap::remove(self, &key.unwrap());
+  }
+  fn get(&self, at: usize) -> u64 { self.range((at as u64) << 32 ..).next().map(|(_, &x)| x).unwrap_or(0) }
+  fn split_append(&mut self, at: usize) {
+    let mut right = self.split_off(&((at as u64) << 32));
+    self.append(&mut right);
+  }
+  fn sum(&self) -> u64 { self.values().sum() }
+  fn insert_sorted(&mut self, x: u64) { BTreeMap::insert(self, x << 32 | self.len() as u64, x); }
+}
+
+/// Repeats `f` up to `MAX_OPS` times or until `BUDGET` is spent, and returns nanoseconds per call.
+fn repeat(mut f: impl FnMut()) -> f64 {
+  let start = Instant::now();
+  let mut ops = 0;
+  while ops < MAX_OPS {
+    f();
+    ops += 1;
+    if ops % 16 == 0 && start.elapsed() > BUDGET {
+      break;
+    }
+  }
+  start.elapsed().as_nanos() as f64 / ops as f64
+}
+
+/// Times a single pass over `n` elements and returns nanoseconds per element.
+fn per_element(n: usize, f: impl FnOnce()) -> f64 {
+  let start = Instant::now();
+  f();
+  start.elapsed().as_nanos() as f64 / n as f64
+}
+
+const OPERATIONS: [&str; 7] = ["push_back", "from_iter", "iter", "index", "insert+remove", "split+append", "insert_sorted+remove"];
+
+fn run<S: Subject>(n: usize) -> [f64; 7] {
+  let mut rng = Rng(n as u64);
+  let push = per_element(n, || {
+    let mut s = S::new();
+    for i in 0 .. n as u64 {
+      s.push_back(i);
+    }
+    black_box(s.len());
+  });
+  let mut s = S::build(0);
+  let build = per_element(n, || s = S::build(n));
+  let iter = per_element(n, || { black_box(s.sum()); });
+  let index = repeat(|| { black_box(s.get(rng.below(n))); });
+  let insert_remove = repeat(|| {
+    s.insert(rng.below(n + 1), rng.below(n) as u64);
+    s.remove(rng.below(n));
+  });
+  let split = repeat(|| s.split_append(rng.below(n + 1)));
+  let mut s = S::build(n);
+  let sorted = repeat(|| {
+    s.insert_sorted(rng.below(n) as u64);
+    s.remove(rng.below(n));
+  });
+  black_box(s.len());
+  [push, build, iter, index, insert_remove, split, sorted]
+}
+
+fn main() {
+  let markdown = std::env::args().any(|arg| arg == "--markdown");
+  let max_exponent = std::env::args().skip(1).find_map(|arg| arg.parse::<u32>().ok()).unwrap_or(7);
+  let names = [TreeList::<u64>::NAME, Vec::<u64>::NAME, VecDeque::<u64>::NAME, BTreeMap::<u64, u64>::NAME];
+  // Warm up the allocator and caches so the first measurement is comparable.
+  black_box((run::<TreeList<u64>>(1000), run::<Vec<u64>>(1000), run::<VecDeque<u64>>(1000), run::<BTreeMap<u64, u64>>(1000)));
+  if !markdown {
+    println!("size,operation,structure,ns");
+  }
+  for exponent in 3 ..= max_exponent {
+    let n = 10usize.pow(exponent);
+    let results = [run::<TreeList<u64>>(n), run::<Vec<u64>>(n), run::<VecDeque<u64>>(n), run::<BTreeMap<u64, u64>>(n)];
+    if markdown {
+      println!("\n### n = 10^{} (ns/op)\n", exponent);
+      println!("| operation | {} |", names.join(" | "));
+      println!("|---|{}", "---:|".repeat(names.len()));
+      for (i, operation) in OPERATIONS.iter().enumerate() {
+        let row = results.iter().map(|r| format!("{:.1}", r[i])).collect::<Vec<_>>();
+        println!("| {} | {} |", operation, row.join(" | "));
+      }
+    } else {
+      for (i, operation) in OPERATIONS.iter().enumerate() {
+        for (name, r) in names.iter().zip(&results) {
+          println!("{},{},{},{:.1}", n, operation, name, r[i]);
+        }
+      }
+    }
+  }
+}
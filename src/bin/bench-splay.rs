@@ -0,0 +1,44 @@
+use std::time::Instant;
+use tree_list::{*, balance::*};
+
+const N: usize = 1_000_000;
+
+fn bench<B: Balance>(name: &str) {
+  let mut seed = 1u64;
+  let mut next = |n: usize| {
+    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    (seed >> 33) as usize % n
+  };
+  let mut list = (0 .. N).collect::<TreeList<_, B>>();
+  let mut sum = 0;
+
+  let start = Instant::now();
+  for i in 0 .. N {
+    sum += list.access(i);
+  }
+  let sequential = start.elapsed();
+
+  let start = Instant::now();
+  let mut cursor = N / 2;
+  for i in 0 .. N {
+    cursor = (cursor + next(9)).saturating_sub(4).min(list.len() - 1);
+    if i % 8 == 0 {
+      list.insert(cursor, i);
+    } else {
+      sum += list.access(cursor);
+    }
+  }
+  let local = start.elapsed();
+
+  let start = Instant::now();
+  for _ in 0 .. N {
+    sum += list.access(next(list.len()));
+  }
+  let random = start.elapsed();
+  println!("{:<6} sequential {:>10.2?} local {:>10.2?} random {:>10.2?} ({})", name, sequential, local, random, sum % 10);
+}
+
+fn main() {
+  bench::<Avl>("Avl");
+  bench::<Splay>("Splay");
+}
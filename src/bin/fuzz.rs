@@ -0,0 +1,34 @@
+//! Runs `tree_list::fuzz::run` without cargo-fuzz.
+//!
+//! `fuzz FILE...` replays the given inputs; without arguments it runs random inputs
+//! (`FUZZ_ITERATIONS`, default 100000) and writes a failing input to `fuzz-failure.bin`.
+
+use std::{env, fs, panic};
+
+fn main() {
+  let files = env::args().skip(1).collect::<Vec<_>>();
+  if !files.is_empty() {
+    for file in files {
+      let data = fs::read(&file).unwrap();
+      tree_list::fuzz::run(&data);
+      println!("{}: ok", file);
+    }
+    return;
+  }
+  let iterations = env::var("FUZZ_ITERATIONS").ok().and_then(|n| n.parse().ok()).unwrap_or(100_000);
+  let mut seed = env::var("FUZZ_SEED").ok().and_then(|n| n.parse().ok()).unwrap_or(1u64);
+  let mut next = || {
+    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    (seed >> 33) as usize
+  };
+  for iteration in 0 .. iterations {
+    let len = next() % 2048;
+    let data = (0 .. len).map(|_| next() as u8).collect::<Vec<_>>();
+    if panic::catch_unwind(|| tree_list::fuzz::run(&data)).is_err() {
+      fs::write("fuzz-failure.bin", &data).unwrap();
+      eprintln!("iteration {} failed; input written to fuzz-failure.bin", iteration);
+      std::process::exit(1);
+    }
+  }
+  println!("{} inputs ok", iterations);
+}
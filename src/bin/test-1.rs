@@ -1,7 +1,7 @@
 use tree_list::*;
 
 fn main() {
-    let mut list = TreeList::new();
+    let mut list = TreeList::<_>::new();
     list.push_back(2);
     list.push_back(3);
     list.push_back(4);
@@ -0,0 +1,608 @@
+use core::{ops::*, iter::FromIterator, fmt::Debug, mem::MaybeUninit, ptr, slice};
+use alloc::vec;
+
+use crate::{balance::Avl, iter, monoid::{Aggregate, Monoid}, node::{self, Node, PinnedNode}};
+
+/// An inline array of at most `B` elements.
+pub struct Chunk<T, const B: usize> {
+    len: usize,
+    data: [MaybeUninit<T>; B],
+}
+
+impl<T, const B: usize> Chunk<T, B> {
+    /// Evaluated for every `B` that `new` is used with, so that a chunk of fewer than two elements does not compile.
+    const VALID: () = assert!(B >= 2, "a chunk must hold at least two elements");
+
+    pub fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        Self {
+            len: 0,
+            data: core::array::from_fn(|_| MaybeUninit::uninit()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == B
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.data.as_ptr() as *const T, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr() as *mut T, self.len) }
+    }
+
+    pub fn push(&mut self, element: T) {
+        self.insert(self.len, element);
+    }
+
+    pub fn insert(&mut self, index: usize, element: T) {
+        assert!(index <= self.len && self.len < B);
+        unsafe {
+            let p = self.data.as_mut_ptr().add(index);
+            ptr::copy(p, p.add(1), self.len - index);
+            p.write(MaybeUninit::new(element));
+        }
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len);
+        let element = unsafe {
+            let p = self.data.as_mut_ptr().add(index);
+            let element = p.read().assume_init();
+            ptr::copy(p.add(1), p, self.len - index - 1);
+            element
+        };
+        self.len -= 1;
+        element
+    }
+
+    /// Moves all the elements of `other` to the end of `self`.
+    pub fn append(&mut self, other: &mut Self) {
+        assert!(self.len + other.len <= B);
+        unsafe {
+            ptr::copy_nonoverlapping(other.data.as_ptr(), self.data.as_mut_ptr().add(self.len), other.len);
+        }
+        self.len += other.len;
+        other.len = 0;
+    }
+
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len);
+        let mut other = Self::new();
+        unsafe {
+            ptr::copy_nonoverlapping(self.data.as_ptr().add(at), other.data.as_mut_ptr(), self.len - at);
+        }
+        other.len = self.len - at;
+        self.len = at;
+        other
+    }
+}
+
+impl<T, const B: usize> Default for Chunk<T, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const B: usize> Drop for Chunk<T, B> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) }
+    }
+}
+
+/// Counts the elements in the chunks of every subtree.
+struct Elements;
+
+impl Monoid for Elements {
+    type Value = usize;
+
+    fn identity() -> usize {
+        0
+    }
+
+    fn op(a: &usize, b: &usize) -> usize {
+        a + b
+    }
+}
+
+impl<T, const B: usize> Aggregate<Chunk<T, B>> for Elements {
+    fn lift(chunk: &Chunk<T, B>) -> usize {
+        chunk.len()
+    }
+}
+
+/// A node of a `ChunkedTreeList`. Its length is the number of chunks, and its aggregate the number of elements.
+type ChunkNode<T, const B: usize> = Node<Chunk<T, B>, Avl, Elements>;
+type Tree<T, const B: usize> = node::Tree<Chunk<T, B>, Avl, Elements>;
+
+fn leaf<T, const B: usize>(chunk: Chunk<T, B>) -> PinnedNode<Chunk<T, B>, Avl, Elements> {
+    Node::pin(chunk, &mut ())
+}
+
+fn elements<T, const B: usize>(node: Option<&ChunkNode<T, B>>) -> usize {
+    node.map(|node| *node.sum()).unwrap_or(0)
+}
+
+/// Changes the chunk of a node through `f`, then updates the node.
+fn modify<T, const B: usize, R>(node: &mut PinnedNode<Chunk<T, B>, Avl, Elements>, f: impl FnOnce(&mut Chunk<T, B>) -> R) -> R {
+    let node_mut = unsafe { node.as_mut().get_unchecked_mut() };
+    let result = f(node_mut.data_mut());
+    node_mut.update();
+    result
+}
+
+fn from_iter<T, const B: usize, I: IntoIterator<Item = T>>(data: I) -> Tree<T, B> {
+    let mut nodes = vec![];
+    let mut chunk = Chunk::new();
+    for element in data {
+        if chunk.is_full() {
+            nodes.push(leaf(core::mem::take(&mut chunk)));
+        }
+        chunk.push(element);
+    }
+    let node = Node::build(nodes);
+    if chunk.is_empty() {
+        node
+    } else {
+        concat(node, Some(leaf(chunk)))
+    }
+}
+
+fn at<T, const B: usize>(mut node: &ChunkNode<T, B>, mut index: usize) -> &T {
+    loop {
+        let left_len = elements(node.child(false));
+        let own = node.data().len();
+        if index < left_len {
+            node = node.child(false).unwrap();
+        } else if index < left_len + own {
+            return &node.data().as_slice()[index - left_len];
+        } else {
+            index -= left_len + own;
+            node = node.child(true).unwrap();
+        }
+    }
+}
+
+fn at_mut<T, const B: usize>(root: &mut PinnedNode<Chunk<T, B>, Avl, Elements>, mut index: usize) -> &mut T {
+    let mut node = unsafe { root.as_mut().get_unchecked_mut() };
+    loop {
+        let left_len = elements(node.child(false));
+        let own = node.data().len();
+        let (left, chunk, right) = node.parts_mut();
+        if index < left_len {
+            node = left.unwrap();
+        } else if index < left_len + own {
+            return &mut chunk.as_mut_slice()[index - left_len];
+        } else {
+            index -= left_len + own;
+            node = right.unwrap();
+        }
+    }
+}
+
+/// Concatenates `left` and `right` like `merge`, then merges or evens out the chunks around the seam
+/// until each of them holds at least `B / 2` elements or it is the only chunk.
+/// Keeps that invariant for the whole list when it holds for both operands apart from their end chunks.
+fn concat<T, const B: usize>(left: Tree<T, B>, right: Tree<T, B>) -> Tree<T, B> {
+    let (mut left, mut a) = pop_last(left);
+    let (mut b, mut right) = pop_first(right);
+    loop {
+        if a.is_none() {
+            a = b.take();
+            let (first, rest) = pop_first(right);
+            b = first;
+            right = rest;
+        }
+        if b.is_none() {
+            b = a.take();
+            let (rest, last) = pop_last(left);
+            left = rest;
+            a = last;
+        }
+        let (mut first, mut second) = match (a.take(), b.take()) {
+            (Some(first), Some(second)) => (first, second),
+            // Both sides are empty, so this is the only chunk.
+            (first, second) => return first.or(second),
+        };
+        let total = first.data().len() + second.data().len();
+        if total <= B {
+            let mut moved = modify(&mut second, core::mem::take);
+            modify(&mut first, |chunk| chunk.append(&mut moved));
+            if first.data().len() >= B / 2 || (left.is_none() && right.is_none()) {
+                return Some(Node::join(left, first, right));
+            }
+            // The merged chunk is still short; merge it with one more neighbor.
+            if left.is_some() {
+                let (rest, last) = pop_last(left);
+                left = rest;
+                a = last;
+                b = Some(first);
+            } else {
+                a = Some(first);
+            }
+        } else {
+            let first_len = first.data().len();
+            if first_len < B / 2 {
+                let mut moved = modify(&mut second, |chunk| {
+                    let rest = chunk.split_off(total / 2 - first_len);
+                    core::mem::replace(chunk, rest)
+                });
+                modify(&mut first, |chunk| chunk.append(&mut moved));
+            } else if second.data().len() < B / 2 {
+                let mut tail = modify(&mut first, |chunk| chunk.split_off(total / 2));
+                modify(&mut second, |chunk| {
+                    tail.append(chunk);
+                    *chunk = tail;
+                });
+            }
+            return Some(Node::join(Some(Node::join(left, first, None)), second, right));
+        }
+    }
+}
+
+fn pop_first<T, const B: usize>(tree: Tree<T, B>) -> (Tree<T, B>, Tree<T, B>) {
+    match tree {
+        Some(node) => node.split_at(1),
+        None => (None, None),
+    }
+}
+
+fn pop_last<T, const B: usize>(tree: Tree<T, B>) -> (Tree<T, B>, Tree<T, B>) {
+    match tree {
+        Some(node) => {
+            let count = node.len();
+            node.split_at(count - 1)
+        }
+        None => (None, None),
+    }
+}
+
+/// Splits the subtree before the `at`-th element, cutting a chunk if needed.
+/// The pieces of a cut chunk may hold fewer than `B / 2` elements; `concat` repairs them.
+fn split_at<T, const B: usize>(node: PinnedNode<Chunk<T, B>, Avl, Elements>, at: usize) -> (Tree<T, B>, Tree<T, B>) {
+    let len = *node.sum();
+    assert!(at <= len);
+    if at == 0 {
+        return (None, Some(node));
+    } else if at == len {
+        return (Some(node), None);
+    }
+    let left_len = elements(node.child(false));
+    let own = node.data().len();
+    let (left, mut pivot, right) = node.detach();
+    if at <= left_len {
+        let (left, middle) = split_at(left.unwrap(), at);
+        (left, Some(Node::join(middle, pivot, right)))
+    } else if at >= left_len + own {
+        let (middle, right) = split_at(right.unwrap(), at - left_len - own);
+        (Some(Node::join(left, pivot, middle)), right)
+    } else {
+        let tail = modify(&mut pivot, |chunk| chunk.split_off(at - left_len));
+        (Some(Node::join(left, pivot, None)), Some(Node::join(None, leaf(tail), right)))
+    }
+}
+
+/// Inserts `element` before the `index`-th element.
+/// A full chunk is split in half, and its upper half becomes a new node.
+fn insert<T, const B: usize>(mut node: PinnedNode<Chunk<T, B>, Avl, Elements>, index: usize, element: T) -> PinnedNode<Chunk<T, B>, Avl, Elements> {
+    let left_len = elements(node.child(false));
+    let own = node.data().len();
+    if index < left_len {
+        let left = node.replace_child(false, None).unwrap();
+        node.replace_child(false, Some(insert(left, index, element)));
+    } else if index <= left_len + own {
+        let offset = index - left_len;
+        let tail = modify(&mut node, |chunk| {
+            if chunk.is_full() {
+                let mut tail = chunk.split_off(B / 2);
+                if offset <= B / 2 {
+                    chunk.insert(offset, element);
+                } else {
+                    tail.insert(offset - B / 2, element);
+                }
+                Some(tail)
+            } else {
+                chunk.insert(offset, element);
+                None
+            }
+        });
+        if let Some(tail) = tail {
+            let right = node.replace_child(true, None);
+            node.replace_child(true, Node::merge(Some(leaf(tail)), right));
+        }
+    } else {
+        let right = node.replace_child(true, None).unwrap();
+        node.replace_child(true, Some(insert(right, index - left_len - own, element)));
+    }
+    unsafe { node.as_mut().get_unchecked_mut() }.update();
+    node.balance()
+}
+
+/// A list that stores up to `B` elements inline in each node.
+/// Small elements need far less memory than in `TreeList`, and iteration is cache friendly.
+/// Every chunk holds at least `B / 2` elements unless the list has a single chunk.
+pub struct ChunkedTreeList<T, const B: usize = 32> {
+    root: Tree<T, B>,
+}
+
+impl<T, const B: usize> ChunkedTreeList<T, B> {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        elements(self.root.as_deref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of chunks, i.e. the number of allocated nodes.
+    pub fn chunk_count(&self) -> usize {
+        self.root.as_ref().map(|node| node.len()).unwrap_or(0)
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+
+    /// Inserts element at the given index.
+    /// # Panics
+    /// Panics if index is out of bounds (i.e. greater than the length of the list).
+    /// # Complexity
+    /// O(log(len) + B)
+    pub fn insert(&mut self, index: usize, element: T) {
+        assert!(index <= self.len());
+        self.root = Some(if let Some(root) = self.root.take() {
+            insert(root, index, element)
+        } else {
+            let mut chunk = Chunk::new();
+            chunk.push(element);
+            leaf(chunk)
+        });
+    }
+
+    pub fn push_front(&mut self, element: T) {
+        self.insert(0, element);
+    }
+
+    pub fn push_back(&mut self, element: T) {
+        self.insert(self.len(), element);
+    }
+
+    /// Removes the element at the specified position in the list.
+    /// Returns the element that was removed.
+    /// A chunk left with fewer than `B / 2` elements is merged with a neighbor.
+    /// # Complexity
+    /// O(log(len) + B)
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        assert!(index < self.len());
+        let (left, right) = split_at(self.root.take()?, index);
+        let (first, right) = pop_first(right);
+        let mut first = first.unwrap();
+        let element = modify(&mut first, |chunk| chunk.remove(0));
+        let right = if first.data().is_empty() { right } else { Some(Node::join(None, first, right)) };
+        self.root = concat(left, right);
+        Some(element)
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            self.remove(0)
+        }
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            self.remove(self.len() - 1)
+        }
+    }
+
+    /// Moves all the elements of `other` into `Self`, leaving `other` empty.
+    /// # Complexity
+    /// O(log(len + other.len()))
+    pub fn append(&mut self, other: &mut Self) {
+        self.root = concat(self.root.take(), other.root.take());
+    }
+
+    /// Splits the list into two at the given index.
+    /// Returns the second list.
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    /// # Complexity
+    /// O(log(len) + B)
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len());
+        if let Some(root) = self.root.take() {
+            let (left, right) = split_at(root, at);
+            self.root = concat(left, None);
+            Self { root: concat(None, right) }
+        } else {
+            Self::new()
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, B> {
+        Iter {
+            chunks: iter::Iter::new(self.root.as_deref()),
+            chunk: [].iter(),
+        }
+    }
+}
+
+impl<T, const B: usize> Default for ChunkedTreeList<T, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const B: usize> FromIterator<T> for ChunkedTreeList<T, B> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            root: from_iter(iter),
+        }
+    }
+}
+
+impl<T, const B: usize> Index<usize> for ChunkedTreeList<T, B> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        assert!(index < self.len());
+        at(self.root.as_ref().unwrap(), index)
+    }
+}
+
+impl<T, const B: usize> IndexMut<usize> for ChunkedTreeList<T, B> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        assert!(index < self.len());
+        at_mut(self.root.as_mut().unwrap(), index)
+    }
+}
+
+impl<T: Debug, const B: usize> Debug for ChunkedTreeList<T, B> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("ChunkedTreeList ")?;
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, T, const B: usize> {
+    chunks: iter::Iter<'a, Chunk<T, B>, Avl, Elements>,
+    chunk: slice::Iter<'a, T>,
+}
+impl<'a, T, const B: usize> Iterator for Iter<'a, T, B> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(element) = self.chunk.next() {
+                return Some(element);
+            }
+            self.chunk = self.chunks.next()?.as_slice().iter();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChunkedTreeList, ChunkNode};
+
+    /// Checks the AVL heights and that every chunk but a lone one is at least half full.
+    fn check<T, const B: usize>(list: &ChunkedTreeList<T, B>) {
+        fn walk<T, const B: usize>(node: Option<&ChunkNode<T, B>>, lone: bool) -> usize {
+            match node {
+                Some(node) => {
+                    assert!(lone || node.data().len() >= B / 2, "chunk of {} elements", node.data().len());
+                    let (l, r) = (walk(node.child(false), false), walk(node.child(true), false));
+                    assert!(l.max(r) - l.min(r) <= 1 && *node.tag() as usize == l.max(r) + 1);
+                    *node.tag() as usize
+                }
+                None => 0,
+            }
+        }
+        walk(list.root.as_deref(), list.chunk_count() == 1);
+    }
+
+    #[test]
+    fn balanced_chunks() {
+        let mut list = ChunkedTreeList::<usize, 8>::new();
+        for i in 0 .. 50000 {
+            let mut single = ChunkedTreeList::new();
+            single.push_back(i);
+            single.append(&mut list);
+            list = single;
+        }
+        check(&list);
+        assert!(*list.root.as_ref().unwrap().tag() as f64 <= 1.44 * (list.chunk_count() as f64).log2() + 2.0);
+        let mut seed = 5u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        let mut expected = list.iter().copied().collect::<Vec<_>>();
+        for _ in 0 .. 2000 {
+            let (a, b) = (next(expected.len() + 1), next(expected.len() + 1));
+            let mut right = list.split_off(a);
+            check(&list);
+            check(&right);
+            let mut tail = right.split_off(b.min(right.len()));
+            tail.append(&mut list);
+            tail.append(&mut right);
+            list = tail;
+            let b = b.min(expected.len() - a);
+            let mut rotated = expected.split_off(a);
+            let rest = rotated.split_off(b);
+            expected = rest.into_iter().chain(expected).chain(rotated).collect();
+            if !expected.is_empty() {
+                let at = next(expected.len());
+                assert_eq!(list.remove(at), Some(expected.remove(at)));
+            }
+            check(&list);
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+        assert!(list.chunk_count() <= expected.len() / 4 + 1);
+    }
+
+    #[test]
+    fn insert_remove() {
+        let mut list = ChunkedTreeList::<usize, 4>::new();
+        let mut expected = vec![];
+        for i in 0 .. 200 {
+            let at = (i * 13) % (expected.len() + 1);
+            list.insert(at, i);
+            expected.insert(at, i);
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+        for i in 0 .. 150 {
+            let at = (i * 7) % expected.len();
+            assert_eq!(list.remove(at), Some(expected.remove(at)));
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+        for (i, &x) in expected.iter().enumerate() {
+            assert_eq!(list[i], x);
+        }
+    }
+
+    #[test]
+    fn split_append() {
+        let mut list = (0 .. 100).collect::<ChunkedTreeList<_, 8>>();
+        let mut right = list.split_off(37);
+        assert_eq!(list.len(), 37);
+        assert_eq!(right.len(), 63);
+        right.append(&mut list);
+        assert_eq!(right.iter().copied().collect::<Vec<_>>(), (37 .. 100).chain(0 .. 37).collect::<Vec<_>>());
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn drop_elements() {
+        let rc = std::rc::Rc::new(());
+        let mut list = (0 .. 50).map(|_| rc.clone()).collect::<ChunkedTreeList<_, 8>>();
+        let right = list.split_off(20);
+        list.remove(3);
+        drop(right);
+        assert_eq!(std::rc::Rc::strong_count(&rc), 20);
+        drop(list);
+        assert_eq!(std::rc::Rc::strong_count(&rc), 1);
+    }
+}
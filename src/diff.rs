@@ -0,0 +1,222 @@
+//! Edit scripts between two lists, computed by Myers' O((N + M) D) algorithm in linear space.
+
+use super::*;
+use alloc::vec;
+
+/// One edit of a script returned by `TreeList::diff`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Edit<T> {
+    /// Inserts `elements` before the `index`-th element.
+    Insert { index: usize, elements: Vec<T> },
+    /// Removes `len` elements starting at `index`.
+    Delete { index: usize, len: usize },
+    /// Replaces `len` elements starting at `index` with `elements`.
+    Replace { index: usize, len: usize, elements: Vec<T> },
+}
+
+/// Returns a shortest sequence of single-element steps turning `a` into `b`, in order.
+/// Each step `(x, y, insert)` starts at the point `(x, y)` and either inserts `b[y]` or deletes `a[x]`.
+fn steps<T: Eq>(a: &[&T], b: &[&T]) -> Vec<(usize, usize, bool)> {
+    let mut forward = vec![0; a.len() + b.len() + 4];
+    let mut backward = forward.clone();
+    let mut steps = vec![];
+    conquer(a, b, (0, 0), &mut forward, &mut backward, &mut steps);
+    steps
+}
+
+/// Appends the steps turning `a` into `b`, which start at the point `origin` of the whole problem.
+/// Splits the problem at a middle snake and recurses on both halves, so the memory stays linear.
+fn conquer<T: Eq>(a: &[&T], b: &[&T], origin: (usize, usize), forward: &mut [isize], backward: &mut [isize], steps: &mut Vec<(usize, usize, bool)>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[prefix ..], &b[prefix ..]);
+    let suffix = a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[.. a.len() - suffix], &b[.. b.len() - suffix]);
+    let (x, y) = (origin.0 + prefix, origin.1 + prefix);
+    if a.is_empty() {
+        steps.extend((0 .. b.len()).map(|j| (x, y + j, true)));
+    } else if b.is_empty() {
+        steps.extend((0 .. a.len()).map(|i| (x + i, y, false)));
+    } else {
+        let (i, j) = middle_snake(a, b, forward, backward);
+        conquer(&a[.. i], &b[.. j], (x, y), forward, backward, steps);
+        conquer(&a[i ..], &b[j ..], (x + i, y + j), forward, backward, steps);
+    }
+}
+
+/// Returns the start of a middle snake, a point on a shortest path that leaves about half of the steps on each side.
+/// `a` and `b` must be non-empty and differ in their first and in their last elements.
+/// Searches from both ends at once: `forward[k]` and `backward[k]` are the furthest x reached on the diagonal k = x - y
+/// from `(0, 0)`, and from `(n, m)` in the reversed lists.
+fn middle_snake<T: Eq>(a: &[&T], b: &[&T], forward: &mut [isize], backward: &mut [isize]) -> (usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta & 1 == 1;
+    let max = (n + m + 1) / 2;
+    let at = |k: isize| (k + max + 1) as usize;
+    forward[at(1)] = 0;
+    backward[at(1)] = 0;
+    for d in 0 ..= max {
+        for k in (-d ..= d).step_by(2) {
+            let v = &forward;
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) { v[at(k + 1)] } else { v[at(k - 1)] + 1 };
+            let start = x;
+            while x < n && x - k < m && a[x as usize] == b[(x - k) as usize] {
+                x += 1;
+            }
+            forward[at(k)] = x;
+            // The diagonal k from the start is the diagonal delta - k from the end.
+            if odd && (k - delta).abs() < d && x + backward[at(delta - k)] >= n {
+                return (start as usize, (start - k) as usize);
+            }
+        }
+        for k in (-d ..= d).step_by(2) {
+            let v = &backward;
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) { v[at(k + 1)] } else { v[at(k - 1)] + 1 };
+            while x < n && x - k < m && a[(n - 1 - x) as usize] == b[(m - 1 - x + k) as usize] {
+                x += 1;
+            }
+            backward[at(k)] = x;
+            if !odd && (k - delta).abs() <= d && x + forward[at(delta - k)] >= n {
+                return ((n - x) as usize, (m - x + k) as usize);
+            }
+        }
+    }
+    unreachable!("the searches meet after at most (n + m) / 2 steps each")
+}
+
+impl<T: Eq + Clone, B: Balance, M: Aggregate<T>, O: Observer<T>> TreeList<T, B, M, O> {
+    /// Returns an edit script turning `self` into `other`, with the fewest inserted and deleted elements.
+    /// The edits are sorted by decreasing index, so every index refers to `self` as well as
+    /// to the list after the preceding edits. `self.apply_patch(&self.diff(&other))` makes `self` equal to `other`.
+    /// # Complexity
+    /// O((len + other.len) D) time and O(len + other.len) space where D is the number of inserted and deleted elements
+    pub fn diff<P: Observer<T>>(&self, other: &TreeList<T, B, M, P>) -> Vec<Edit<T>> {
+        let a = self.iter().collect::<Vec<_>>();
+        let b = other.iter().collect::<Vec<_>>();
+        let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+        let suffix = a[prefix ..].iter().rev().zip(b[prefix ..].iter().rev()).take_while(|(x, y)| x == y).count();
+        let (a, b) = (&a[prefix .. a.len() - suffix], &b[prefix .. b.len() - suffix]);
+        let mut edits = vec![];
+        let mut steps = steps(a, b).into_iter().peekable();
+        while let Some((x, y, insert)) = steps.next() {
+            // Consecutive steps without a common element between them form one edit.
+            let (mut end_x, mut end_y) = if insert { (x, y + 1) } else { (x + 1, y) };
+            while let Some(&(next_x, next_y, insert)) = steps.peek() {
+                if (next_x, next_y) != (end_x, end_y) {
+                    break;
+                }
+                if insert { end_y += 1 } else { end_x += 1 }
+                steps.next();
+            }
+            let index = prefix + x;
+            let elements = b[y .. end_y].iter().map(|&element| element.clone()).collect::<Vec<_>>();
+            edits.push(match (end_x - x, elements.is_empty()) {
+                (0, _) => Edit::Insert { index, elements },
+                (len, true) => Edit::Delete { index, len },
+                (len, false) => Edit::Replace { index, len, elements },
+            });
+        }
+        edits.reverse();
+        edits
+    }
+
+    /// Applies the edits in order.
+    /// # Panics
+    /// Panics if an edit is out of bounds.
+    pub fn apply_patch(&mut self, script: &[Edit<T>]) {
+        for edit in script {
+            match edit {
+                Edit::Insert { index, elements } => {
+                    self.splice(*index .. *index, elements.iter().cloned());
+                }
+                Edit::Delete { index, len } => {
+                    self.splice(*index .. index + len, None);
+                }
+                Edit::Replace { index, len, elements } => {
+                    self.splice(*index .. index + len, elements.iter().cloned());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut seed = 5u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0 .. 300 {
+            let a = (0 .. next(30)).map(|_| next(4)).collect::<TreeList<_>>();
+            let mut b = a.iter().copied().collect::<Vec<_>>();
+            for _ in 0 .. next(6) {
+                match next(3) {
+                    0 => b.insert(next(b.len() + 1), next(4)),
+                    1 if !b.is_empty() => {
+                        b.remove(next(b.len()));
+                    }
+                    _ => b.extend((0 .. next(4)).map(|_| next(4))),
+                }
+            }
+            let b = b.into_iter().collect::<TreeList<_>>();
+            let script = a.diff(&b);
+            let mut patched = a.iter().copied().collect::<TreeList<_>>();
+            patched.apply_patch(&script);
+            assert!(patched.iter().eq(b.iter()), "{:?} -> {:?} by {:?}", a, b, script);
+            assert!(script.windows(2).all(|w| match (&w[0], &w[1]) {
+                (Edit::Insert { index: i, .. } | Edit::Delete { index: i, .. } | Edit::Replace { index: i, .. },
+                 Edit::Insert { index: j, .. } | Edit::Delete { index: j, .. } | Edit::Replace { index: j, .. }) => i > j,
+            }));
+        }
+    }
+
+    fn size<T>(script: &[Edit<T>]) -> usize {
+        script.iter().map(|edit| match edit {
+            Edit::Insert { elements, .. } => elements.len(),
+            Edit::Delete { len, .. } => *len,
+            Edit::Replace { len, elements, .. } => len + elements.len(),
+        }).sum()
+    }
+
+    #[test]
+    fn minimal_script() {
+        let a = "abcabba".chars().collect::<TreeList<_>>();
+        let b = "cbabac".chars().collect::<TreeList<_>>();
+        assert_eq!(size(&a.diff(&b)), 5);
+        assert_eq!(a.diff(&a), vec![]);
+        let empty = TreeList::new();
+        assert_eq!(empty.diff(&b), vec![Edit::Insert { index: 0, elements: "cbabac".chars().collect() }]);
+        assert_eq!(b.diff(&empty), vec![Edit::Delete { index: 0, len: 6 }]);
+    }
+
+    /// Compares the size of the script with the longest common subsequence found by dynamic programming.
+    #[test]
+    fn shortest_against_lcs() {
+        let mut seed = 11u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0 .. 300 {
+            let a = (0 .. next(60)).map(|_| next(3)).collect::<Vec<_>>();
+            let b = (0 .. next(60)).map(|_| next(3)).collect::<Vec<_>>();
+            let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+            for i in 0 .. a.len() {
+                for j in 0 .. b.len() {
+                    lcs[i + 1][j + 1] = if a[i] == b[j] { lcs[i][j] + 1 } else { lcs[i][j + 1].max(lcs[i + 1][j]) };
+                }
+            }
+            let (a, b) = (a.into_iter().collect::<TreeList<_>>(), b.into_iter().collect::<TreeList<_>>());
+            let script = a.diff(&b);
+            assert_eq!(size(&script), a.len() + b.len() - 2 * lcs[a.len()][b.len()]);
+            let mut patched = a.iter().copied().collect::<TreeList<_>>();
+            patched.apply_patch(&script);
+            assert!(patched.iter().eq(b.iter()));
+        }
+    }
+}
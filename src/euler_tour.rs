@@ -0,0 +1,267 @@
+//! Dynamic forest connectivity with Euler tour trees.
+//!
+//! Each tree of the forest is stored as its Euler tour: a sequence with one node per vertex
+//! and one node per direction of each edge. Linking and cutting become splits and merges of the sequences.
+//! A vertex or an edge has to be located from its handle, which `TreeList` and `balance::Splay` cannot do
+//! without parent links, so the sequences are the splay trees of `arena`; all operations take amortized O(log n).
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{arena::{SplayArena, Summarize}, monoid::Monoid};
+
+struct TourNode<M: Monoid> {
+    /// `false` for the node of an edge.
+    vertex: bool,
+    value: M::Value,
+}
+
+impl<M: Monoid> Summarize for TourNode<M> {
+    /// Number of vertex nodes and aggregate of their values.
+    type Summary = (usize, M::Value);
+
+    fn summary(&self) -> Self::Summary {
+        (self.vertex as usize, self.value.clone())
+    }
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        (left.0 + right.0, M::op(&left.1, &right.1))
+    }
+}
+
+/// A forest on vertices `0 .. len` whose edges can be added and removed.
+/// `M` aggregates values attached to the vertices.
+pub struct EulerTourForest<M: Monoid = ()> {
+    /// Vertex `v` is node `v`; edge nodes follow.
+    tours: SplayArena<TourNode<M>>,
+    vertices: usize,
+    /// Node of the directed edge `(u, v)`.
+    edges: BTreeMap<(usize, usize), usize>,
+}
+
+impl EulerTourForest {
+    /// Creates a forest of `n` isolated vertices.
+    pub fn new(n: usize) -> Self {
+        Self::with_values((0 .. n).map(|_| ()).collect())
+    }
+}
+
+impl<M: Monoid> EulerTourForest<M> {
+    /// Creates a forest of isolated vertices with the given values.
+    pub fn with_values(values: Vec<M::Value>) -> Self {
+        let vertices = values.len();
+        let mut tours = SplayArena::new();
+        for value in values {
+            tours.alloc(TourNode { vertex: true, value });
+        }
+        Self { tours, vertices, edges: BTreeMap::new() }
+    }
+
+    /// Returns the number of vertices.
+    pub fn len(&self) -> usize {
+        self.vertices
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices == 0
+    }
+
+    /// Adds the edge `(u, v)`. Returns `false` and does nothing if `u` and `v` are already connected.
+    pub fn link(&mut self, u: usize, v: usize) -> bool {
+        if self.connected(u, v) {
+            return false;
+        }
+        let uv = self.edge_node(u, v);
+        let vu = self.edge_node(v, u);
+        let u_tour = self.reroot(u);
+        let v_tour = self.reroot(v);
+        let tour = self.tours.merge(u_tour, uv);
+        let tour = self.tours.merge(tour, v_tour);
+        self.tours.merge(tour, vu);
+        true
+    }
+
+    /// Removes the edge `(u, v)`. Returns `false` if there is no such edge.
+    pub fn cut(&mut self, u: usize, v: usize) -> bool {
+        let (mut first, mut second) = match (self.edges.remove(&(u, v)), self.edges.remove(&(v, u))) {
+            (Some(uv), Some(vu)) => (uv, vu),
+            _ => return false,
+        };
+        if self.tours.position(first) > self.tours.position(second) {
+            core::mem::swap(&mut first, &mut second);
+        }
+        // The tour is `a first b second c`; `b` becomes a tree of its own and `a c` remains.
+        let (a, _) = self.tours.split_before(first);
+        self.tours.split_after(first);
+        self.tours.split_before(second);
+        let (_, c) = self.tours.split_after(second);
+        self.tours.merge(a, c);
+        self.tours.release(first);
+        self.tours.release(second);
+        true
+    }
+
+    pub fn has_edge(&self, u: usize, v: usize) -> bool {
+        self.edges.contains_key(&(u, v))
+    }
+
+    pub fn connected(&mut self, u: usize, v: usize) -> bool {
+        assert!(u < self.vertices && v < self.vertices);
+        if u == v {
+            return true;
+        }
+        // After `v` is splayed, `u` is the root of its tree only if it is in another tour.
+        self.tours.position(u);
+        self.tours.position(v);
+        !self.tours.is_root(u)
+    }
+
+    /// Returns the number of vertices connected to `v`.
+    pub fn component_size(&mut self, v: usize) -> usize {
+        self.component(v, |sum| sum.0)
+    }
+
+    /// Returns the aggregate of the values of the vertices connected to `v`.
+    pub fn component_sum(&mut self, v: usize) -> M::Value {
+        self.component(v, |sum| sum.1.clone())
+    }
+
+    /// Returns the number of vertices in the subtree of `v` when the tree is rooted at its neighbor `parent`.
+    /// # Panics
+    /// Panics if there is no edge `(v, parent)`.
+    pub fn subtree_size(&mut self, v: usize, parent: usize) -> usize {
+        self.subtree(v, parent, |sum| sum.0)
+    }
+
+    /// Returns the aggregate of the subtree of `v` when the tree is rooted at its neighbor `parent`.
+    /// # Panics
+    /// Panics if there is no edge `(v, parent)`.
+    pub fn subtree_sum(&mut self, v: usize, parent: usize) -> M::Value {
+        self.subtree(v, parent, |sum| sum.1.clone())
+    }
+
+    pub fn value(&self, v: usize) -> &M::Value {
+        assert!(v < self.vertices);
+        &self.tours.get(v).value
+    }
+
+    pub fn set_value(&mut self, v: usize, value: M::Value) {
+        assert!(v < self.vertices);
+        self.tours.modify(v, |node| node.value = value);
+    }
+
+    /// Applies `f` to the summary of the whole tour of `v`.
+    fn component<R>(&mut self, v: usize, f: impl FnOnce(&(usize, M::Value)) -> R) -> R {
+        self.tours.position(v);
+        f(self.tours.sum(v).unwrap())
+    }
+
+    fn subtree<R>(&mut self, v: usize, parent: usize, f: impl FnOnce(&(usize, M::Value)) -> R) -> R {
+        let down = self.edges[&(parent, v)];
+        let up = self.edges[&(v, parent)];
+        // After rerooting at `parent`, the tour of the subtree lies between `down` and `up`.
+        self.reroot(parent);
+        let (left, _) = self.tours.split_after(down);
+        let (middle, right) = self.tours.split_before(up);
+        let result = f(self.tours.sum(middle).unwrap());
+        let tour = self.tours.merge(left, middle);
+        self.tours.merge(tour, right);
+        result
+    }
+
+    fn edge_node(&mut self, u: usize, v: usize) -> usize {
+        let x = self.tours.alloc(TourNode { vertex: false, value: M::identity() });
+        self.edges.insert((u, v), x);
+        x
+    }
+
+    /// Rotates the tour of `v` so that it starts at `v`; returns the new root.
+    fn reroot(&mut self, v: usize) -> usize {
+        let (before, v) = self.tours.split_before(v);
+        self.tours.merge(v, before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{arena::NIL, monoid::Additive};
+
+    /// Finds the component of each vertex by searching the edge list.
+    fn components(n: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+        let mut component = vec![NIL; n];
+        for s in 0 .. n {
+            if component[s] != NIL {
+                continue;
+            }
+            let mut stack = vec![s];
+            component[s] = s;
+            while let Some(u) = stack.pop() {
+                for &(a, b) in edges {
+                    for &(x, y) in &[(a, b), (b, a)] {
+                        if x == u && component[y] == NIL {
+                            component[y] = s;
+                            stack.push(y);
+                        }
+                    }
+                }
+            }
+        }
+        component
+    }
+
+    #[test]
+    fn random_links_and_cuts() {
+        let n = 30;
+        let mut forest = EulerTourForest::<Additive<usize>>::with_values((0 .. n).collect());
+        let mut edges = vec![];
+        let mut seed = 1u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0 .. 2000 {
+            let (u, v) = (next(n), next(n));
+            if next(3) > 0 {
+                let linked = forest.link(u, v);
+                let component = components(n, &edges);
+                assert_eq!(linked, component[u] != component[v]);
+                if linked {
+                    edges.push((u, v));
+                }
+            } else if !edges.is_empty() {
+                let (u, v) = edges.swap_remove(next(edges.len()));
+                assert!(forest.cut(v, u));
+                assert!(!forest.has_edge(u, v));
+            }
+            let component = components(n, &edges);
+            let (a, b) = (next(n), next(n));
+            assert_eq!(forest.connected(a, b), component[a] == component[b]);
+            let members = (0 .. n).filter(|&x| component[x] == component[a]).collect::<Vec<_>>();
+            assert_eq!(forest.component_size(a), members.len());
+            assert_eq!(forest.component_sum(a), members.iter().sum::<usize>());
+        }
+    }
+
+    #[test]
+    fn subtree() {
+        // 0 - 1 - 2
+        //     |
+        //     3 - 4
+        let mut forest = EulerTourForest::<Additive<i64>>::with_values(vec![1, 10, 100, 1000, 10000]);
+        for &(u, v) in &[(0, 1), (1, 2), (1, 3), (3, 4)] {
+            assert!(forest.link(u, v));
+        }
+        assert!(!forest.link(2, 4));
+        assert_eq!(forest.subtree_size(1, 0), 4);
+        assert_eq!(forest.subtree_sum(3, 1), 11000);
+        assert_eq!(forest.subtree_sum(1, 3), 111);
+        forest.set_value(4, -10000);
+        assert_eq!(forest.subtree_sum(3, 1), -9000);
+        assert!(forest.cut(1, 3));
+        assert!(!forest.cut(1, 3));
+        assert!(!forest.connected(0, 4));
+        assert_eq!(forest.component_size(4), 2);
+        assert_eq!(forest.component_sum(0), 111);
+        assert_eq!(EulerTourForest::new(3).component_size(2), 1);
+    }
+}
@@ -0,0 +1,144 @@
+//! C interface for `TreeList<i64>` and `TreeList<*mut c_void>`.
+//!
+//! The declarations are in `ffi/include/tree_list.h`; the `tree-list-ffi` crate in `ffi/`
+//! builds them as a shared and a static library.
+//! Lists are handed out as opaque pointers and must be released with the matching `*_free`.
+//! Out-of-bounds indices are reported through the return values instead of panicking.
+
+use core::ffi::c_void;
+use alloc::boxed::Box;
+
+use crate::TreeList;
+
+pub type TreeListI64 = TreeList<i64>;
+pub type TreeListPtr = TreeList<*mut c_void>;
+
+macro_rules! ffi {
+    ($list:ty, $t:ty, $new:ident, $free:ident, $len:ident, $get:ident, $set:ident, $insert:ident,
+     $push_back:ident, $remove:ident, $split_off:ident, $append:ident, $for_each:ident) => {
+        /// Creates an empty list.
+        #[no_mangle]
+        pub extern "C" fn $new() -> *mut $list {
+            Box::into_raw(Box::new(TreeList::new()))
+        }
+
+        /// Frees a list created by this library. Does nothing for null.
+        /// # Safety
+        /// `list` must be null or a list not freed yet.
+        #[no_mangle]
+        pub unsafe extern "C" fn $free(list: *mut $list) {
+            if !list.is_null() {
+                drop(Box::from_raw(list));
+            }
+        }
+
+        /// # Safety
+        /// `list` must be a valid list.
+        #[no_mangle]
+        pub unsafe extern "C" fn $len(list: *const $list) -> usize {
+            (*list).len()
+        }
+
+        /// Writes the `index`-th element to `out`. Returns `false` if `index` is out of bounds.
+        /// # Safety
+        /// `list` must be a valid list and `out` must be writable.
+        #[no_mangle]
+        pub unsafe extern "C" fn $get(list: *const $list, index: usize, out: *mut $t) -> bool {
+            match (*list).get(index) {
+                Some(&value) => {
+                    *out = value;
+                    true
+                }
+                None => false,
+            }
+        }
+
+        /// Replaces the `index`-th element. Returns `false` if `index` is out of bounds.
+        /// # Safety
+        /// `list` must be a valid list.
+        #[no_mangle]
+        pub unsafe extern "C" fn $set(list: *mut $list, index: usize, value: $t) -> bool {
+            match (*list).get_mut(index) {
+                Some(mut element) => {
+                    *element = value;
+                    true
+                }
+                None => false,
+            }
+        }
+
+        /// Inserts `value` before the `index`-th element. Returns `false` if `index` is greater than the length.
+        /// # Safety
+        /// `list` must be a valid list.
+        #[no_mangle]
+        pub unsafe extern "C" fn $insert(list: *mut $list, index: usize, value: $t) -> bool {
+            if index > (*list).len() {
+                return false;
+            }
+            (*list).insert(index, value);
+            true
+        }
+
+        /// # Safety
+        /// `list` must be a valid list.
+        #[no_mangle]
+        pub unsafe extern "C" fn $push_back(list: *mut $list, value: $t) {
+            (*list).push_back(value);
+        }
+
+        /// Removes the `index`-th element and writes it to `out` unless `out` is null.
+        /// Returns `false` if `index` is out of bounds.
+        /// # Safety
+        /// `list` must be a valid list and `out` must be null or writable.
+        #[no_mangle]
+        pub unsafe extern "C" fn $remove(list: *mut $list, index: usize, out: *mut $t) -> bool {
+            if index >= (*list).len() {
+                return false;
+            }
+            let value = (*list).remove(index).unwrap();
+            if !out.is_null() {
+                *out = value;
+            }
+            true
+        }
+
+        /// Moves the elements from `at` into a new list and returns it, or null if `at` is greater than the length.
+        /// # Safety
+        /// `list` must be a valid list.
+        #[no_mangle]
+        pub unsafe extern "C" fn $split_off(list: *mut $list, at: usize) -> *mut $list {
+            if at > (*list).len() {
+                return core::ptr::null_mut();
+            }
+            Box::into_raw(Box::new((*list).split_off(at)))
+        }
+
+        /// Moves all the elements of `other` to the end of `list`, leaving `other` empty but not freed.
+        /// # Safety
+        /// `list` and `other` must be distinct valid lists.
+        #[no_mangle]
+        pub unsafe extern "C" fn $append(list: *mut $list, other: *mut $list) {
+            (*list).append(&mut *other);
+        }
+
+        /// Calls `callback(element, context)` for each element in order until it returns `false`.
+        /// # Safety
+        /// `list` must be a valid list that `callback` does not modify.
+        #[no_mangle]
+        pub unsafe extern "C" fn $for_each(list: *const $list, callback: extern "C" fn($t, *mut c_void) -> bool, context: *mut c_void) {
+            for &value in (*list).iter() {
+                if !callback(value, context) {
+                    break;
+                }
+            }
+        }
+    };
+}
+
+ffi!(TreeListI64, i64, tree_list_i64_new, tree_list_i64_free, tree_list_i64_len, tree_list_i64_get, tree_list_i64_set,
+    tree_list_i64_insert, tree_list_i64_push_back, tree_list_i64_remove, tree_list_i64_split_off, tree_list_i64_append,
+    tree_list_i64_for_each);
+
+ffi!(TreeListPtr, *mut c_void, tree_list_ptr_new, tree_list_ptr_free, tree_list_ptr_len, tree_list_ptr_get, tree_list_ptr_set,
+    tree_list_ptr_insert, tree_list_ptr_push_back, tree_list_ptr_remove, tree_list_ptr_split_off, tree_list_ptr_append,
+    tree_list_ptr_for_each);
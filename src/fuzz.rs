@@ -0,0 +1,290 @@
+//! Byte-driven fuzzing of the mutating operations.
+//!
+//! `run` decodes arbitrary bytes into operations, applies them to a `TreeList` of each balancing strategy
+//! and to a `Vec`, and panics when they disagree. Elements are boxed so that Miri sees every allocation.
+//!
+//! Only built with the `fuzz` feature. With cargo-fuzz:
+//! ```ignore
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| tree_list::fuzz::run(data));
+//! ```
+//! Without it, `cargo run --release --features fuzz --bin fuzz` runs random inputs and `cargo run --features fuzz --bin fuzz -- FILE...` replays inputs.
+
+use core::ops::Bound::{self, *};
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{TreeList, balance::*};
+
+/// Reads the input; once it is exhausted every read returns zero.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((&byte, rest)) => {
+                self.data = rest;
+                byte
+            }
+            None => 0,
+        }
+    }
+
+    /// Returns a value in `0 ..= max`, choosing the ends often.
+    fn position(&mut self, max: usize) -> usize {
+        match self.byte() % 4 {
+            0 => 0,
+            1 => max,
+            _ => (usize::from(self.byte()) << 8 | usize::from(self.byte())) % (max + 1),
+        }
+    }
+
+    /// Returns a range in `0 .. len` using any combination of bounds.
+    fn range(&mut self, len: usize) -> (Bound<usize>, Bound<usize>) {
+        let mut start = self.position(len);
+        let mut end = self.position(len);
+        if start > end {
+            core::mem::swap(&mut start, &mut end);
+        }
+        let start_bound = match self.byte() % 3 {
+            0 => Included(start),
+            1 if start > 0 => Excluded(start - 1),
+            _ if start == 0 => Unbounded,
+            _ => Included(start),
+        };
+        let end_bound = match self.byte() % 3 {
+            0 => Excluded(end),
+            1 if end > 0 => Included(end - 1),
+            _ if end == len => Unbounded,
+            _ => Excluded(end),
+        };
+        (start_bound, end_bound)
+    }
+}
+
+fn check<B: Balance>(list: &TreeList<Box<u32>, B>, expected: &[Box<u32>]) {
+    assert_eq!(list.len(), expected.len());
+    assert!(list.iter().eq(expected.iter()));
+}
+
+/// Applies the operations decoded from `data` to a `TreeList<_, B>` and a `Vec`.
+pub fn run_with<B: Balance>(data: &[u8]) {
+    let mut input = Reader { data };
+    let mut lists = [TreeList::<Box<u32>, B>::default(), TreeList::default()];
+    let mut vecs: [Vec<Box<u32>>; 2] = [Vec::new(), Vec::new()];
+    let mut counter = 0;
+    let mut next = || {
+        counter += 1;
+        Box::new(counter)
+    };
+    while !input.data.is_empty() {
+        let op = input.byte();
+        let i = usize::from(op >> 7);
+        let (list, expected) = (&mut lists[i], &mut vecs[i]);
+        let len = expected.len();
+        match op & 0x7f {
+            0 ..= 9 => {
+                let at = input.position(len);
+                let x = next();
+                list.insert(at, x.clone());
+                expected.insert(at, x);
+            }
+            10 ..= 17 if len > 0 => {
+                let at = input.position(len - 1);
+                assert_eq!(list.remove(at), Some(expected.remove(at)));
+            }
+            18 ..= 23 => {
+                let x = next();
+                list.push_front(x.clone());
+                expected.insert(0, x);
+            }
+            24 ..= 29 => {
+                let x = next();
+                list.push_back(x.clone());
+                expected.push(x);
+            }
+            30 ..= 33 => {
+                let x = if expected.is_empty() { None } else { Some(expected.remove(0)) };
+                assert_eq!(list.pop_front(), x);
+            }
+            34 ..= 37 => assert_eq!(list.pop_back(), expected.pop()),
+            38 ..= 45 => {
+                let at = input.position(len);
+                lists[1 - i] = lists[i].split_off(at);
+                vecs[1 - i] = vecs[i].split_off(at);
+            }
+            46 ..= 53 => {
+                let (a, b) = lists.split_at_mut(1);
+                let (ea, eb) = vecs.split_at_mut(1);
+                if i == 0 {
+                    a[0].append(&mut b[0]);
+                    ea[0].append(&mut eb[0]);
+                } else {
+                    b[0].append(&mut a[0]);
+                    eb[0].append(&mut ea[0]);
+                }
+            }
+            54 ..= 65 => {
+                let range = input.range(len);
+                let replace_with = (0 .. input.byte() % 4).map(|_| next()).collect::<Vec<_>>();
+                let removed = list.splice(range, replace_with.iter().cloned());
+                let expected_removed = expected.splice(range, replace_with).collect::<Vec<_>>();
+                check(&removed, &expected_removed);
+            }
+            66 ..= 73 => {
+                let range = input.range(len);
+                let removed = match input.byte() % 3 {
+                    0 => list.drain(range).collect::<Vec<_>>(),
+                    1 => {
+                        let mut removed = list.drain(range).rev().collect::<Vec<_>>();
+                        removed.reverse();
+                        removed
+                    }
+                    _ => {
+                        // Drops the iterator after taking at most one element.
+                        let mut drain = list.drain(range);
+                        drain.next().into_iter().collect()
+                    }
+                };
+                let expected_removed = expected.drain(range).collect::<Vec<_>>();
+                assert_eq!(removed[..], expected_removed[.. removed.len()]);
+            }
+            74 ..= 79 => {
+                list.reverse();
+                expected.reverse();
+            }
+            80 ..= 87 if len > 0 => {
+                let at = input.position(len - 1);
+                match op % 3 {
+                    0 => {
+                        let x = next();
+                        *list.get_mut(at).unwrap() = x.clone();
+                        expected[at] = x;
+                    }
+                    1 => {
+                        let x = next();
+                        list[at] = x.clone();
+                        expected[at] = x;
+                    }
+                    _ => assert_eq!(list.access(at), &expected[at]),
+                }
+            }
+            88 ..= 95 => {
+                let at = input.position(len);
+                assert!(list.iter_from(at).eq(expected[at ..].iter()));
+                assert_eq!(list.get(at), expected.get(at));
+            }
+            96 ..= 99 => {
+                list.clear();
+                expected.clear();
+            }
+            100 ..= 103 => list.reserve(usize::from(input.byte())),
+            104 ..= 105 => list.shrink_to_fit(),
+            106 ..= 111 => {
+                let range = input.range(len);
+                let moved = expected.drain(range).collect::<Vec<_>>();
+                let dest = input.position(expected.len());
+                expected.splice(dest .. dest, moved);
+                list.move_range(range, dest);
+            }
+            112 ..= 117 => {
+                let (mut a, mut b) = (input.position(len), input.position(len));
+                if a > b {
+                    core::mem::swap(&mut a, &mut b);
+                }
+                let (mut c, mut d) = (input.position(len - b), input.position(len - b));
+                if c > d {
+                    core::mem::swap(&mut c, &mut d);
+                }
+                let (first, second) = ((Included(a), Excluded(b)), (Included(b + c), Excluded(b + d)));
+                let tail = expected.split_off(b + d);
+                let second_elements = expected.split_off(b + c);
+                let middle = expected.split_off(b);
+                let first_elements = expected.split_off(a);
+                expected.extend(second_elements.into_iter().chain(middle).chain(first_elements).chain(tail));
+                if op & 1 == 0 {
+                    list.swap_ranges(first, second);
+                } else {
+                    list.swap_ranges(second, first);
+                }
+            }
+            118 ..= 121 => {
+                let range = input.range(len);
+                expected.extend_from_within(range);
+                list.extend_from_within(range);
+            }
+            122 ..= 123 => {
+                // Sorts by a coarse key so that stability matters.
+                list.sort_by_key(|x| **x % 4);
+                expected.sort_by_key(|x| **x % 4);
+            }
+            124 => {
+                list.sort_unstable();
+                expected.sort_unstable();
+                assert!(list.is_sorted());
+            }
+            125 => {
+                let mut swaps = Vec::new();
+                list.shuffle(|n| {
+                    let j = usize::from(input.byte()) % n;
+                    swaps.push((n - 1, j));
+                    j
+                });
+                for (i, j) in swaps {
+                    expected.swap(i, j);
+                }
+            }
+            126 => {
+                let script = lists[i].diff(&lists[1 - i]);
+                lists[i].apply_patch(&script);
+                vecs[i] = vecs[1 - i].clone();
+            }
+            _ => {}
+        }
+        check(&lists[0], &vecs[0]);
+        check(&lists[1], &vecs[1]);
+        assert!(lists[0].capacity() >= lists[0].len());
+    }
+}
+
+/// Runs `run_with` for every balancing strategy.
+pub fn run(data: &[u8]) {
+    run_with::<Avl>(data);
+    run_with::<Treap>(data);
+    run_with::<WeightBalanced>(data);
+    run_with::<Splay>(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splice_bounds() {
+        let mut list = (0 .. 10).collect::<TreeList<_>>();
+        let removed = list.splice((Excluded(2), Included(4)), vec![20]);
+        assert_eq!(removed.iter().copied().collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2, 20, 5, 6, 7, 8, 9]);
+        assert_eq!(list.split_off(0).len(), 9);
+        assert!(list.is_empty());
+        list.splice(.., vec![]);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn random_inputs() {
+        let (count, max_len) = if cfg!(miri) { (8, 64) } else { (300, 600) };
+        let mut seed = 1u64;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        for _ in 0 .. count {
+            let len = next() % max_len;
+            let data = (0 .. len).map(|_| next() as u8).collect::<Vec<_>>();
+            run(&data);
+        }
+        run(&[]);
+        run(&[0x30, 0x40, 0x50, 0x60, 0x70]);
+    }
+}
@@ -0,0 +1,224 @@
+//! Polynomial rolling hash mod 2^61 - 1 kept as an aggregate of `TreeList<T: Symbol>`,
+//! for comparing substrings of an editable string in O(log(len)).
+//!
+//! Every subtree keeps the hash of its elements read forwards and backwards,
+//! so a range can also be checked for being a palindrome.
+
+use core::ops::RangeBounds;
+
+use super::bounds;
+use crate::{balance::{Avl, Balance}, monoid::{Aggregate, Monoid}, Observer, TreeList};
+
+const MOD: u64 = (1 << 61) - 1;
+/// Fixed base, so hashes of different lists are comparable.
+const BASE: u64 = 0x0a3c_5e7f_9b1d_2f43;
+
+fn mul(a: u64, b: u64) -> u64 {
+    let t = a as u128 * b as u128;
+    let x = (t >> 61) as u64 + (t as u64 & MOD);
+    if x >= MOD { x - MOD } else { x }
+}
+
+fn add(a: u64, b: u64) -> u64 {
+    let x = a + b;
+    if x >= MOD { x - MOD } else { x }
+}
+
+/// A value that can be hashed by `RollingHash`.
+pub trait Symbol: Clone {
+    /// Returns a number smaller than 2^61 - 1 identifying the symbol.
+    fn code(&self) -> u64;
+}
+
+impl Symbol for u8 {
+    fn code(&self) -> u64 {
+        *self as u64 + 1
+    }
+}
+
+impl Symbol for char {
+    fn code(&self) -> u64 {
+        *self as u64 + 1
+    }
+}
+
+/// Hash of a sequence together with `BASE^len`, so that hashes can be concatenated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Hash {
+    value: u64,
+    pow: u64,
+}
+
+impl Hash {
+    const EMPTY: Self = Self { value: 0, pow: 1 };
+
+    fn concat(self, other: Self) -> Self {
+        Self { value: add(mul(self.value, other.pow), other.value), pow: mul(self.pow, other.pow) }
+    }
+}
+
+/// Hashes of a range read forwards and backwards; the value of the `RollingHash` aggregate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Hashes {
+    forward: Hash,
+    backward: Hash,
+}
+
+impl Hashes {
+    /// Returns the hash of the range as defined by `TreeList::hash`.
+    pub fn value(&self) -> u64 {
+        self.forward.value
+    }
+}
+
+/// Aggregate keeping the polynomial rolling hash of every subtree in both directions.
+pub struct RollingHash;
+
+impl Monoid for RollingHash {
+    type Value = Hashes;
+
+    fn identity() -> Hashes {
+        Hashes { forward: Hash::EMPTY, backward: Hash::EMPTY }
+    }
+
+    fn op(a: &Hashes, b: &Hashes) -> Hashes {
+        Hashes { forward: a.forward.concat(b.forward), backward: b.backward.concat(a.backward) }
+    }
+}
+
+impl<T: Symbol> Aggregate<T> for RollingHash {
+    fn lift(element: &T) -> Hashes {
+        let hash = Hash { value: element.code(), pow: BASE };
+        Hashes { forward: hash, backward: hash }
+    }
+}
+
+/// A list of symbols that can compare any two ranges in O(log(len)) by rolling hashes.
+/// Equal hashes mean equal contents with high probability.
+pub type HashedTreeList<T, B = Avl> = TreeList<T, B, RollingHash>;
+
+impl<T: Symbol, B: Balance, O: Observer<T>> TreeList<T, B, RollingHash, O> {
+    /// Returns the hash of `range`, that is `sum(code(x[i]) * BASE^(r - 1 - i)) mod 2^61 - 1`.
+    /// Hashes of different lists are comparable.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn hash<R: RangeBounds<usize>>(&self, range: R) -> u64 {
+        self.fold(range).value()
+    }
+
+    /// Returns whether the contents of the two ranges are equal.
+    /// # Panics
+    /// Panics if either range is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn substring_eq<R: RangeBounds<usize>, S: RangeBounds<usize>>(&self, a: R, b: S) -> bool {
+        let (a, b) = (bounds(a, self.len()), bounds(b, self.len()));
+        a.1 - a.0 == b.1 - b.0 && self.fold(a.0 .. a.1).forward == self.fold(b.0 .. b.1).forward
+    }
+
+    /// Returns the length of the longest common prefix of the suffixes starting at `i` and `j`.
+    /// # Panics
+    /// Panics if `i` or `j` is greater than `len`.
+    /// # Complexity
+    /// O(log(len)^2)
+    pub fn lcp(&self, i: usize, j: usize) -> usize {
+        let n = self.len();
+        assert!(i <= n && j <= n);
+        let (mut ok, mut ng) = (0, n - i.max(j) + 1);
+        while ng - ok > 1 {
+            let mid = (ok + ng) / 2;
+            if self.fold(i .. i + mid).forward == self.fold(j .. j + mid).forward {
+                ok = mid;
+            } else {
+                ng = mid;
+            }
+        }
+        ok
+    }
+
+    /// Returns whether `range` reads the same in both directions.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn is_palindrome<R: RangeBounds<usize>>(&self, range: R) -> bool {
+        let hashes = self.fold(range);
+        hashes.forward == hashes.backward
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_operations() {
+        let mut list = HashedTreeList::<_>::new();
+        let mut expected: Vec<u8> = vec![];
+        let mut seed = 3u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0 .. 2000 {
+            let n = expected.len();
+            let (a, b) = (next(n + 1), next(n + 1));
+            let (l, r) = (a.min(b), a.max(b));
+            match next(6) {
+                0 | 1 => {
+                    let value = b'a' + next(2) as u8;
+                    list.insert(a, value);
+                    expected.insert(a, value);
+                }
+                2 if n > 0 => {
+                    let at = next(n);
+                    assert_eq!(list.remove(at), Some(expected.remove(at)));
+                }
+                3 => {
+                    let mut right = list.split_off(r);
+                    let mut middle = list.split_off(l);
+                    middle.reverse();
+                    list.append(&mut middle);
+                    list.append(&mut right);
+                    expected[l .. r].reverse();
+                }
+                4 => {
+                    let mut right = list.split_off(a);
+                    let mut expected_right = expected.split_off(a);
+                    assert_eq!(right.iter().copied().collect::<Vec<_>>(), expected_right);
+                    list.append(&mut right);
+                    expected.append(&mut expected_right);
+                }
+                _ => {
+                    let k = next(n - l.max(a) + 1);
+                    assert_eq!(list.substring_eq(l .. l + k, a .. a + k), expected[l .. l + k] == expected[a .. a + k]);
+                    let lcp = expected[l ..].iter().zip(&expected[a ..]).take_while(|(x, y)| x == y).count();
+                    assert_eq!(list.lcp(l, a), lcp);
+                    let palindrome = expected[l .. r].iter().eq(expected[l .. r].iter().rev());
+                    assert_eq!(list.is_palindrome(l .. r), palindrome);
+                }
+            }
+            assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+        }
+        for (i, x) in expected.iter().enumerate() {
+            assert_eq!(&list[i], x);
+        }
+    }
+
+    #[test]
+    fn chars() {
+        let mut list = "たけやぶやけた".chars().collect::<HashedTreeList<_>>();
+        assert!(list.is_palindrome(..));
+        assert!(!list.substring_eq(0 .. 3, 4 .. 7));
+        let mut tail = list.split_off(4);
+        tail.reverse();
+        list.append(&mut tail);
+        assert!(!list.is_palindrome(..));
+        assert!(list.substring_eq(0 .. 3, 4 .. 7));
+        assert_eq!(list.lcp(0, 4), 3);
+        let other = "けや".chars().collect::<HashedTreeList<_>>();
+        assert_eq!(list.hash(1 .. 3), other.hash(..));
+    }
+}
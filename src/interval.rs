@@ -0,0 +1,282 @@
+//! Interval map (a.k.a. Chtholly tree / ODT) over positions `0 .. len`.
+//! Unlike ODTs on ordered maps, the segments are stored in a run tree indexed by position,
+//! so positions can be inserted or removed and every later segment shifts accordingly.
+//! The tree is the run tree of `rle` without merging equal neighbors: a `Node` tree whose aggregate
+//! counts the positions of the segments, so the segment containing a position is found in O(log(segments)).
+
+use core::{ops::*, fmt::Debug};
+
+use super::bounds;
+use super::rle::{self, concat, len, run, run_count, split_at, RunIter, Runs};
+
+/// A sequence of positions partitioned into segments, each holding a value.
+/// Segments are only split when needed and are never merged implicitly, so `T` needs no `Eq`.
+pub struct IntervalMap<T> {
+    root: Runs<T>,
+}
+
+impl<T: Clone> IntervalMap<T> {
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    /// Creates a map of `len` positions covered by a single segment holding `value`.
+    pub fn filled(len: usize, value: T) -> Self {
+        let root = if len > 0 { Some(run(value, len)) } else { None };
+        Self { root }
+    }
+
+    /// Returns the number of positions.
+    pub fn len(&self) -> usize {
+        len(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns the number of segments.
+    pub fn segment_count(&self) -> usize {
+        run_count(&self.root)
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        rle::get(&self.root, index)
+    }
+
+    /// Inserts `count` positions holding `value` as a new segment before the `index`-th position.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(segments))
+    pub fn insert(&mut self, index: usize, count: usize, value: T) {
+        assert!(index <= self.len());
+        if count == 0 {
+            return;
+        }
+        let (left, right) = split_at(self.root.take(), index);
+        self.root = concat(concat(left, Some(run(value, count))), right);
+    }
+
+    /// Appends `count` positions holding `value` as a new segment.
+    pub fn push(&mut self, count: usize, value: T) {
+        if count > 0 {
+            self.root = concat(self.root.take(), Some(run(value, count)));
+        }
+    }
+
+    /// Removes the positions in `range`, shifting the later segments to the left.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(segments)) amortized
+    pub fn remove<R: RangeBounds<usize>>(&mut self, range: R) {
+        let (l, r) = bounds(range, self.len());
+        let (left, rest) = split_at(self.root.take(), l);
+        let (_, right) = split_at(rest, r - l);
+        self.root = concat(left, right);
+    }
+
+    /// Replaces the segments covering `range` with a single segment holding `value`.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(segments)) amortized; each removed segment was created by an earlier call.
+    pub fn assign<R: RangeBounds<usize>>(&mut self, range: R, value: T) {
+        let (l, r) = bounds(range, self.len());
+        if l == r {
+            return;
+        }
+        let (left, rest) = split_at(self.root.take(), l);
+        let (_, right) = split_at(rest, r - l);
+        self.root = concat(concat(left, Some(run(value, r - l))), right);
+    }
+
+    /// Calls `f` with the position range and the value of each segment intersecting `range`,
+    /// after cutting the segments at both ends of `range`.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(segments) + k) where k is the number of segments in `range`
+    pub fn apply_segments<R: RangeBounds<usize>, F: FnMut(Range<usize>, &mut T)>(&mut self, range: R, mut f: F) {
+        let (l, r) = bounds(range, self.len());
+        let (left, rest) = split_at(self.root.take(), l);
+        let (mut middle, right) = split_at(rest, r - l);
+        rle::for_each_mut(&mut middle, l, &mut |start, count, value| f(start .. start + count, value));
+        self.root = concat(concat(left, middle), right);
+    }
+
+    /// Returns an iterator over the position range and the value of each segment intersecting `range`,
+    /// with the ranges clipped to `range`.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    pub fn segments<R: RangeBounds<usize>>(&self, range: R) -> Segments<'_, T> {
+        let (l, r) = bounds(range, self.len());
+        let (runs, start) = RunIter::starting_at(self.root.as_deref(), l);
+        Segments { runs, start, from: l, end: r }
+    }
+
+    /// Moves all the segments of `other` after the ones of `self`, leaving `other` empty.
+    /// # Complexity
+    /// O(log(segments + other.segments))
+    pub fn append(&mut self, other: &mut Self) {
+        self.root = concat(self.root.take(), other.root.take());
+    }
+
+    /// Splits the map into two at the given position. Returns the second map.
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    /// # Complexity
+    /// O(log(segments))
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len());
+        let (left, right) = split_at(self.root.take(), at);
+        self.root = left;
+        Self { root: right }
+    }
+}
+
+impl<T: Clone> Default for IntervalMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> Index<usize> for IntervalMap<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        assert!(index < self.len());
+        self.get(index).unwrap()
+    }
+}
+
+impl<T: Clone + Debug> Debug for IntervalMap<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("IntervalMap ")?;
+        f.debug_list().entries(self.segments(..)).finish()
+    }
+}
+
+pub struct Segments<'a, T> {
+    runs: RunIter<'a, T>,
+    /// Start position of the next run.
+    start: usize,
+    from: usize,
+    end: usize,
+}
+impl<'a, T> Iterator for Segments<'a, T> {
+    type Item = (Range<usize>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.from >= self.end || self.start >= self.end {
+            return None;
+        }
+        let (value, count) = self.runs.next()?;
+        let start = self.start;
+        self.start += count;
+        Some((start.max(self.from) .. self.start.min(self.end), value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+
+    fn check(map: &IntervalMap<u32>, expected: &[u32]) {
+        let mut flat = vec![];
+        let mut position = 0;
+        for (range, &value) in map.segments(..) {
+            assert_eq!(range.start, position);
+            assert!(range.start < range.end);
+            position = range.end;
+            flat.extend(range.map(|_| value));
+        }
+        assert_eq!(flat, expected);
+        assert_eq!(map.segments(..).count(), map.segment_count());
+    }
+
+    #[test]
+    fn random_operations() {
+        let mut map = IntervalMap::filled(20, 0);
+        let mut expected = vec![0; 20];
+        let mut seed = 7u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0 .. 3000 {
+            let value = next(100) as u32;
+            let (a, b) = (next(expected.len() + 1), next(expected.len() + 1));
+            let (l, r) = (a.min(b), a.max(b));
+            match next(6) {
+                0 => {
+                    let count = next(4);
+                    map.insert(a, count, value);
+                    expected.splice(a .. a, core::iter::repeat(value).take(count));
+                }
+                1 => {
+                    map.remove(l .. r);
+                    expected.drain(l .. r);
+                }
+                2 | 3 => {
+                    map.assign(l .. r, value);
+                    expected[l .. r].iter_mut().for_each(|x| *x = value);
+                }
+                4 => {
+                    let mut visited = l;
+                    map.apply_segments(l .. r, |range, x| {
+                        assert_eq!(range.start, visited);
+                        visited = range.end;
+                        *x += 1;
+                    });
+                    assert_eq!(visited, r);
+                    expected[l .. r].iter_mut().for_each(|x| *x += 1);
+                }
+                _ => {
+                    let mut position = l;
+                    for (range, &x) in map.segments(l .. r) {
+                        assert_eq!(range.start, position);
+                        assert!(range.start < range.end);
+                        assert!(expected[range.clone()].iter().all(|&y| y == x));
+                        position = range.end;
+                    }
+                    assert_eq!(position, r.max(l));
+                    let mut right = map.split_off(a);
+                    check(&right, &expected[a ..]);
+                    map.append(&mut right);
+                }
+            }
+            check(&map, &expected);
+        }
+        for (i, x) in expected.iter().enumerate() {
+            assert_eq!(map.get(i), Some(x));
+        }
+    }
+
+    #[test]
+    fn shifts_with_positions() {
+        let mut map = IntervalMap::filled(10, 'a');
+        map.assign(3 .. 6, 'b');
+        map.insert(0, 2, 'c');
+        assert_eq!(map.segments(..).map(|(r, &v)| (r, v)).collect::<Vec<_>>(), vec![(0 .. 2, 'c'), (2 .. 5, 'a'), (5 .. 8, 'b'), (8 .. 12, 'a')]);
+        map.remove(4 .. 6);
+        assert_eq!(map.segments(3 .. 8).map(|(r, &v)| (r, v)).collect::<Vec<_>>(), vec![(3 .. 4, 'a'), (4 .. 6, 'b'), (6 .. 8, 'a')]);
+        map.assign(.., 'z');
+        assert_eq!(map.segment_count(), 1);
+        assert_eq!(map[9], 'z');
+    }
+
+    #[test]
+    fn empty_range() {
+        let map = IntervalMap::filled(10, 'a');
+        assert_eq!(map.segments(3 .. 3).count(), 0);
+        assert_eq!(map.segments(10 ..).count(), 0);
+        assert_eq!(IntervalMap::<char>::new().segments(..).count(), 0);
+    }
+}
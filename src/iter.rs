@@ -1,23 +1,44 @@
 use super::*;
+use alloc::{vec, vec::Vec};
 
-pub struct Iter<'a, T> {
-    stack: Vec<&'a Node<T>>,
+pub struct Iter<'a, T, B: Balance = Avl, M: Aggregate<T> = ()> {
+    stack: Vec<&'a Node<T, B, M>>,
 }
-impl<'a, T> Iter<'a, T> {
-    pub fn new(root: Option<&'a Node<T>>) -> Self {
+impl<'a, T, B: Balance, M: Aggregate<T>> Iter<'a, T, B, M> {
+    pub fn new(root: Option<&'a Node<T, B, M>>) -> Self {
         let mut this = Self { stack: vec![] };
         this.add(root);
         this
     }
 
-    fn add(&mut self, mut node: Option<&'a Node<T>>) {
+    /// Creates an iterator that starts at the `index`-th element of `root`.
+    pub fn starting_at(root: Option<&'a Node<T, B, M>>, mut index: usize) -> Self {
+        let mut this = Self { stack: vec![] };
+        let mut node = root;
+        while let Some(current) = node {
+            let left_len = current.child(false).map(|n| n.len()).unwrap_or(0);
+            if index < left_len {
+                this.stack.push(current);
+                node = current.child(false);
+            } else if index == left_len {
+                this.stack.push(current);
+                break;
+            } else {
+                index -= left_len + 1;
+                node = current.child(true);
+            }
+        }
+        this
+    }
+
+    fn add(&mut self, mut node: Option<&'a Node<T, B, M>>) {
         while let Some(child) = node {
             self.stack.push(child);
             node = child.child(false);
         }
     }
 }
-impl<'a, T> Iterator for Iter<'a, T> {
+impl<'a, T, B: Balance, M: Aggregate<T>> Iterator for Iter<'a, T, B, M> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -26,4 +47,47 @@ impl<'a, T> Iterator for Iter<'a, T> {
         self.add(node.child(true));
         Some(element)
     }
-}
\ No newline at end of file
+}
+/// An iterator that removes a range of a `TreeList`. Created by `TreeList::drain`.
+pub struct Drain<'a, T, B: Balance = Avl, M: Aggregate<T> = (), O: Observer<T> = ()> {
+    list: &'a mut TreeList<T, B, M, O>,
+    tree: Tree<T, B, M>,
+}
+impl<'a, T, B: Balance, M: Aggregate<T>, O: Observer<T>> Drain<'a, T, B, M, O> {
+    pub(crate) fn new(list: &'a mut TreeList<T, B, M, O>, tree: Tree<T, B, M>) -> Self {
+        Self { list, tree }
+    }
+}
+impl<'a, T, B: Balance, M: Aggregate<T>, O: Observer<T>> Iterator for Drain<'a, T, B, M, O> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let (first, rest) = self.tree.take()?.split_at(1);
+        self.tree = rest;
+        Some(self.list.release(first?))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+impl<'a, T, B: Balance, M: Aggregate<T>, O: Observer<T>> DoubleEndedIterator for Drain<'a, T, B, M, O> {
+    fn next_back(&mut self) -> Option<T> {
+        let tree = self.tree.take()?;
+        let at = tree.len() - 1;
+        let (rest, last) = tree.split_at(at);
+        self.tree = rest;
+        Some(self.list.release(last?))
+    }
+}
+impl<'a, T, B: Balance, M: Aggregate<T>, O: Observer<T>> ExactSizeIterator for Drain<'a, T, B, M, O> {
+    fn len(&self) -> usize {
+        self.tree.as_ref().map(|node| node.len()).unwrap_or(0)
+    }
+}
+impl<'a, T, B: Balance, M: Aggregate<T>, O: Observer<T>> Drop for Drain<'a, T, B, M, O> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
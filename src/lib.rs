@@ -1,29 +1,170 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 pub mod node;
+pub mod balance;
 pub mod iter;
+pub mod persistent;
+pub mod rope;
+pub mod chunked;
+mod set;
+pub mod map;
+#[cfg(any(test, feature = "fuzz"))]
+pub mod fuzz;
+pub mod monoid;
+mod arena;
+mod tree;
+pub mod euler_tour;
+pub mod rle;
+pub mod interval;
+pub mod hash;
+pub mod diff;
+pub mod undo;
+pub mod rga;
+pub mod observe;
+#[cfg(feature = "rayon")]
+pub mod par;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(test)]
+mod model;
 // pub mod slice;
 
 // pub type AVLTreeListSlice<'a, T> = slice::Slice<'a, T>;
 
-use std::{ops::*, pin::Pin, iter::FromIterator, fmt::Debug};
+use core::{ops::*, iter::FromIterator, fmt::Debug, cmp::Ordering, mem::MaybeUninit};
+use alloc::{boxed::Box, vec::Vec};
 
 use node::*;
+use balance::{Balance, Avl, Splay};
+use monoid::Aggregate;
+
+pub use persistent::PersistentTreeList;
+pub use rope::Rope;
+pub use chunked::ChunkedTreeList;
+pub use map::TreeMap;
+pub use euler_tour::EulerTourForest;
+pub use rle::RleTreeList;
+pub use interval::IntervalMap;
+pub use hash::HashedTreeList;
+pub use diff::Edit;
+pub use undo::UndoableTreeList;
+pub use rga::Rga;
+pub use observe::{Observer, ElementMut};
+
+/// Converts `range` to `start .. end` for a sequence of length `len`.
+/// # Panics
+/// Panics if the range is decreasing or out of bounds.
+fn bounds<R: RangeBounds<usize>>(range: R, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&n) => n,
+        Bound::Excluded(&n) => n.checked_add(1).expect("range start overflows"),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&n) => n.checked_add(1).expect("range end overflows"),
+        Bound::Excluded(&n) => n,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "range starts at {} but ends at {}", start, end);
+    assert!(end <= len, "range end {} is out of bounds for length {}", end, len);
+    (start, end)
+}
+
+/// A self-adjusting list: recently accessed positions move toward the root.
+/// Use `access` or `get_mut` to get the amortized bounds of a splay tree;
+/// `get`, `Index`, `iter_from`, `leftmost` and `rightmost` take `&self`, do not splay and cost O(depth), up to O(len).
+pub type SplayTreeList<T> = TreeList<T, Splay>;
 
-pub struct TreeList<T> {
-    root: Option<PinnedNode<T>>,
+/// A list backed by a balanced binary tree.
+/// The balancing strategy `B` can be chosen from `balance`; all strategies share the same API.
+/// Nodes released by `remove`, `pop_*`, `drain` and `clear` are kept and reused by later insertions.
+/// Every subtree keeps the aggregate `M` of its elements (see `monoid::Aggregate`), so `fold` of any range is O(log(len));
+/// the default `()` keeps nothing.
+/// The observer `O` is notified of every change (see `observe`); the default `()` ignores them at no cost.
+pub struct TreeList<T, B: Balance = Avl, M: Aggregate<T> = (), O = ()> {
+    root: Option<PinnedNode<T, B, M>>,
+    free: Vec<FreeNode<T, B, M>>,
+    /// State that `B` creates the tags of new nodes from.
+    state: B::State,
+    observer: O,
 }
 
-impl<T> TreeList<T> {
+impl<T, B: Balance, M: Aggregate<T>> TreeList<T, B, M> {
+    /// Creates an empty list. `TreeList::new()` needs the strategy to be known,
+    /// e.g. from `SplayTreeList::new()`, `TreeList::<T, Treap>::new()` or the type of the variable.
     pub fn new() -> Self {
-        Self {
-            root: None,
-        }
+        Self::default()
     }
 
-    pub fn raw(root: Option<PinnedNode<T>>) -> Self {
+    /// Creates an empty list with nodes allocated for at least `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut list = Self::new();
+        list.reserve(capacity);
+        list
+    }
+
+    pub fn raw(root: Option<PinnedNode<T, B, M>>) -> Self {
         Self {
             root,
+            free: Vec::new(),
+            state: B::State::default(),
+            observer: (),
         }
     }
+}
+
+impl<T, B: Balance, M: Aggregate<T>, O: Observer<T>> TreeList<T, B, M, O> {
+    /// Moves the elements into a list that reports its changes to `observer`.
+    pub fn with_observer<P: Observer<T>>(mut self, observer: P) -> TreeList<T, B, M, P> {
+        TreeList {
+            root: self.root.take(),
+            free: core::mem::take(&mut self.free),
+            state: core::mem::take(&mut self.state),
+            observer,
+        }
+    }
+
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    /// Returns the number of elements the list can hold without allocating nodes.
+    pub fn capacity(&self) -> usize {
+        self.len() + self.free.len()
+    }
+
+    /// Allocates nodes so that at least `additional` more elements can be inserted without allocating.
+    pub fn reserve(&mut self, additional: usize) {
+        let missing = additional.saturating_sub(self.free.len());
+        self.free.reserve(missing);
+        for _ in 0 .. missing {
+            self.free.push(Box::new(MaybeUninit::uninit()));
+        }
+    }
+
+    /// Releases the nodes kept for reuse.
+    pub fn shrink_to_fit(&mut self) {
+        self.free = Vec::new();
+    }
+
+    fn alloc(&mut self, element: T) -> PinnedNode<T, B, M> {
+        match self.free.pop() {
+            Some(free) => Node::pin_in(free, element, &mut self.state),
+            None => Node::pin(element, &mut self.state),
+        }
+    }
+
+    fn release(&mut self, node: PinnedNode<T, B, M>) -> T {
+        let (data, free) = node.recycle();
+        self.free.push(free);
+        data
+    }
 
     pub fn len(&self) -> usize {
         self.root.as_ref().map(|node| node.len()).unwrap_or(0)
@@ -34,7 +175,14 @@ impl<T> TreeList<T> {
     }
 
     pub fn clear(&mut self) {
-        self.root = None;
+        self.observer.on_splice(0 .. self.len(), 0);
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            let (left, node, right) = node.detach();
+            stack.extend(left);
+            stack.extend(right);
+            self.release(node);
+        }
     }
 
     /// Inserts element at the given index.
@@ -44,7 +192,8 @@ impl<T> TreeList<T> {
     /// O(log(len))
     pub fn insert(&mut self, index: usize, element: T) {
         assert!(index <= self.len());
-        let node = Node::pin(element);
+        let node = self.alloc(element);
+        self.observer.on_insert(index, node.data());
         if let Some(root) = self.root.take() {
             let (left, right) = root.split_at(index);
             self.root = Node::merge(Node::merge(left, Some(node)), right);
@@ -53,19 +202,39 @@ impl<T> TreeList<T> {
         }
     }
     pub fn push_front(&mut self, element: T) {
-        let node = Node::pin(element);
+        let node = self.alloc(element);
+        self.observer.on_insert(0, node.data());
         self.root = Node::merge(Some(node), self.root.take());
     }
     pub fn push_back(&mut self, element: T) {
-        let node = Node::pin(element);
+        let node = self.alloc(element);
+        self.observer.on_insert(self.len(), node.data());
         self.root = Node::merge(self.root.take(), Some(node));
     }
 
     /// Moves all the elements of `other` into `Self`, leaving `other` empty.
     /// # Complexity
     /// O(log(len + other.len()))
-    pub fn append(&mut self, other: &mut Self) {
-        self.root = Node::merge(self.root.take(), other.root.take());
+    pub fn append<P: Observer<T>>(&mut self, other: &mut TreeList<T, B, M, P>) {
+        if !other.is_empty() {
+            other.observer.on_split(0);
+            self.observer.on_append(self.len(), other.len());
+        }
+        self.append_tree(other.root.take());
+    }
+
+    fn append_tree(&mut self, tree: Tree<T, B, M>) {
+        self.root = Node::merge(self.root.take(), tree);
+    }
+
+    /// Splits off the elements from `at` without notifying the observer.
+    fn split_tree(&mut self, at: usize) -> Tree<T, B, M> {
+        let (left, right) = match self.root.take() {
+            Some(root) => root.split_at(at),
+            None => (None, None),
+        };
+        self.root = left;
+        right
     }
 
     /// Removes the element at the specified position in the list.
@@ -78,34 +247,37 @@ impl<T> TreeList<T> {
         if let Some(right) = right {
             let (node, right) = right.split_at(1);
             self.root = Node::merge(left, right);
-            return Some(unsafe { Pin::into_inner_unchecked(node?) }.into_data());
+            let element = self.release(node?);
+            self.observer.on_remove(index, &element);
+            Some(element)
         } else {
             self.root = left;
-            return None;
+            None
         }
     }
     pub fn pop_front(&mut self) -> Option<T> {
         let (left, right) = self.root.take()?.split_at(1);
         self.root = right;
-        Some(unsafe { Pin::into_inner_unchecked(left?) }.into_data())
+        let element = self.release(left?);
+        self.observer.on_remove(0, &element);
+        Some(element)
     }
     pub fn pop_back(&mut self) -> Option<T> {
         let root = self.root.take()?;
         let index = root.len() - 1;
         let (left, right) = root.split_at(index);
         self.root = left;
-        Some(unsafe { Pin::into_inner_unchecked(right?) }.into_data())
+        let element = self.release(right?);
+        self.observer.on_remove(index, &element);
+        Some(element)
     }
 
+    /// Reverses the order of the elements by rebuilding the tree, which recomputes the aggregates.
+    /// # Complexity
+    /// O(len)
     pub fn reverse(&mut self) {
-        fn dfs<T>(mut node: Pin<Box<Node<T>>>) -> Pin<Box<Node<T>>> {
-            let left = node.replace_child(false, None);
-            let right = node.replace_child(true, None);
-            node.replace_child(true, left.map(dfs));
-            node.replace_child(false, right.map(dfs));
-            node
-        }
-        self.root = self.root.take().map(dfs);
+        self.root = self.root.take().map(Node::reverse);
+        self.observer.on_reverse();
     }
 
     /// Splits the list into two at the given index.
@@ -114,21 +286,56 @@ impl<T> TreeList<T> {
     /// Panics if the index is out of bounds.
     /// # Complexity
     /// O(log(len))
-    pub fn split_off(&mut self, at: usize) -> Self {
+    pub fn split_off(&mut self, at: usize) -> TreeList<T, B, M> {
         assert!(at <= self.len());
-        if let Some(root) = self.root.take() {
-            let (left, right) = root.split_at(at);
-            self.root = left;
-            Self::raw(right)
+        if at < self.len() {
+            self.observer.on_split(at);
+        }
+        TreeList::raw(self.split_tree(at))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len() {
+            Some(self.root.as_ref().unwrap().at(index).data())
         } else {
-            Self::new()
+            None
         }
     }
 
-    pub fn iter(&self) -> iter::Iter<'_, T> {
+    /// Returns the aggregate of the elements in `range`, `M::identity()` if it is empty.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn fold<R: RangeBounds<usize>>(&self, range: R) -> M::Value {
+        let (l, r) = bounds(range, self.len());
+        match &self.root {
+            Some(root) => root.fold(l, r),
+            None => M::identity(),
+        }
+    }
+
+    /// Returns the element at the given index, letting the balancing strategy adapt to the access.
+    /// With `Splay` the element moves to the root, so accessing it or its neighbors next is cheap.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    pub fn access(&mut self, index: usize) -> &T {
+        assert!(index < self.len());
+        let root = B::access(self.root.take().unwrap(), index);
+        self.root.insert(root).at(index).data()
+    }
+
+    pub fn iter(&self) -> iter::Iter<'_, T, B, M> {
         iter::Iter::new(self.root.as_ref().map(|node| node.as_ref().get_ref()))
     }
 
+    /// Returns an iterator starting at the given index.
+    /// # Complexity
+    /// O(log(len)) to start
+    pub fn iter_from(&self, index: usize) -> iter::Iter<'_, T, B, M> {
+        iter::Iter::starting_at(self.root.as_ref().map(|node| node.as_ref().get_ref()), index)
+    }
+
     pub fn leftmost<P: Fn(&T) -> bool>(&self, predicate: P) -> Option<usize> {
         self.root.as_ref().and_then(|node| node.leftmost(|n| predicate(n.data())))
     }
@@ -141,46 +348,226 @@ impl<T> TreeList<T> {
         if let Some(root) = self.root.take() {
             let at = root.leftmost(|y| y.data() >= &x).unwrap_or(root.len());
             let (left, right) = root.split_at(at);
-            self.root = Node::merge(Node::merge(left, Some(Node::pin(x))), right);
+            let node = self.alloc(x);
+            self.observer.on_insert(at, node.data());
+            self.root = Node::merge(Node::merge(left, Some(node)), right);
         } else {
-            self.root = Some(Node::pin(x));
+            let node = self.alloc(x);
+            self.observer.on_insert(0, node.data());
+            self.root = Some(node);
         }
     }
 
-    pub fn splice<R, I>(&mut self, range: R, replace_with: I) -> Self
+    /// Removes the elements in `range` and returns them as an iterator.
+    /// Elements not yet taken are dropped with the iterator, and their nodes are kept for reuse.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(len)) to start, O(log(range length)) per element
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> iter::Drain<'_, T, B, M, O> {
+        let (l, r) = bounds(range, self.len());
+        self.observer.on_splice(l .. r, 0);
+        let (left, right) = match self.root.take() {
+            Some(root) => root.split_at(l),
+            None => (None, None),
+        };
+        let (center, right) = match right {
+            Some(right) => right.split_at(r - l),
+            None => (None, None),
+        };
+        self.root = Node::merge(left, right);
+        iter::Drain::new(self, center)
+    }
+
+    /// Replaces the elements in `range` with `replace_with` and returns the removed elements.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(len) + replace_with.len() log(len))
+    pub fn splice<R, I>(&mut self, range: R, replace_with: I) -> TreeList<T, B, M>
     where
         R: RangeBounds<usize>,
         I: IntoIterator<Item = T>,
     {
-        use Bound::*;
-        let l = match range.start_bound() {
-            Included(&n) => n,
-            Excluded(&n) => n.saturating_sub(1),
-            Unbounded => 0,
-        };
-        let r = match range.end_bound() {
-            Included(&n) => n + 1,
-            Excluded(&n) => n,
-            Unbounded => self.len(),
-        };
-        eprintln!("[{}, {})", l, r);
-        let mut center = self.split_off(l);
-        let mut right = center.split_off(r - l);
+        let (l, r) = bounds(range, self.len());
+        let mut center = TreeList::raw(self.split_tree(l));
+        let right = center.split_tree(r - l);
         for x in replace_with {
-            self.push_back(x);
+            let node = self.alloc(x);
+            self.append_tree(Some(node));
         }
-        self.append(&mut right);
+        let new_len = self.len() - l;
+        self.append_tree(right);
+        self.observer.on_splice(l .. r, new_len);
         center
     }
+
+    /// Moves the elements in `range` so that they start at `dest` in the resulting list.
+    /// # Panics
+    /// Panics if the range is out of bounds, or if `dest` is greater than the length without the range.
+    /// # Complexity
+    /// O(log(len))
+    pub fn move_range<R: RangeBounds<usize>>(&mut self, range: R, dest: usize) {
+        let (l, r) = bounds(range, self.len());
+        assert!(dest <= self.len() - (r - l), "destination {} is out of bounds", dest);
+        let mut moved = TreeList::raw(self.split_tree(l));
+        let right = moved.split_tree(r - l);
+        self.append_tree(right);
+        let right = self.split_tree(dest);
+        self.append_tree(moved.root.take());
+        self.append_tree(right);
+        self.observer.on_move(l .. r, dest);
+    }
+
+    /// Swaps two disjoint ranges, which may have different lengths.
+    /// # Panics
+    /// Panics if the ranges overlap or are out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn swap_ranges<R: RangeBounds<usize>, S: RangeBounds<usize>>(&mut self, a: R, b: S) {
+        let (mut a, mut b) = (bounds(a, self.len()), bounds(b, self.len()));
+        if a.0 > b.0 || (a.0 == b.0 && a.1 > b.1) {
+            core::mem::swap(&mut a, &mut b);
+        }
+        assert!(a.1 <= b.0, "ranges {:?} and {:?} overlap", a.0 .. a.1, b.0 .. b.1);
+        let last = self.split_tree(b.1);
+        let second = self.split_tree(b.0);
+        let middle = self.split_tree(a.1);
+        let first = self.split_tree(a.0);
+        self.append_tree(second);
+        self.append_tree(middle);
+        self.append_tree(first);
+        self.append_tree(last);
+        // Reported as moving `b` to the front of `a`, then `a` to where `b` ended.
+        let b_len = b.1 - b.0;
+        self.observer.on_move(b.0 .. b.1, a.0);
+        self.observer.on_move(a.0 + b_len .. a.1 + b_len, a.0 + b_len + b.0 - a.1);
+    }
+
+    /// Appends clones of the elements in `range` to the end.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(range length + log(len))
+    pub fn extend_from_within<R: RangeBounds<usize>>(&mut self, range: R) where T: Clone {
+        let (l, r) = bounds(range, self.len());
+        let elements = self.iter_from(l).take(r - l).cloned().collect::<Vec<_>>();
+        let nodes = elements.into_iter().map(|x| self.alloc(x)).collect();
+        let len = self.len();
+        self.append_tree(Node::build(nodes));
+        self.observer.on_splice(len .. len, r - l);
+    }
+
+    /// Takes the nodes apart, lets `f` reorder them and rebuilds a balanced tree in O(len).
+    fn rearrange<F: FnOnce(&mut [PinnedNode<T, B, M>])>(&mut self, f: F) {
+        if let Some(root) = self.root.take() {
+            let mut nodes = root.into_nodes();
+            f(&mut nodes);
+            self.root = Node::build(nodes);
+        }
+        self.observer.on_reorder();
+    }
+
+    /// Sorts the list stably. The nodes are reordered; the elements are not moved.
+    /// # Complexity
+    /// O(len log(len))
+    pub fn sort(&mut self) where T: Ord {
+        self.sort_by(T::cmp);
+    }
+
+    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut compare: F) {
+        self.rearrange(|nodes| nodes.sort_by(|a, b| compare(a.data(), b.data())));
+    }
+
+    pub fn sort_by_key<K: Ord, F: FnMut(&T) -> K>(&mut self, mut f: F) {
+        self.rearrange(|nodes| nodes.sort_by_key(|node| f(node.data())));
+    }
+
+    /// Sorts the list without preserving the order of equal elements.
+    /// # Complexity
+    /// O(len log(len))
+    pub fn sort_unstable(&mut self) where T: Ord {
+        self.sort_unstable_by(T::cmp);
+    }
+
+    pub fn sort_unstable_by<F: FnMut(&T, &T) -> Ordering>(&mut self, mut compare: F) {
+        self.rearrange(|nodes| nodes.sort_unstable_by(|a, b| compare(a.data(), b.data())));
+    }
+
+    pub fn sort_unstable_by_key<K: Ord, F: FnMut(&T) -> K>(&mut self, mut f: F) {
+        self.rearrange(|nodes| nodes.sort_unstable_by_key(|node| f(node.data())));
+    }
+
+    /// Shuffles the list with the Fisher-Yates algorithm.
+    /// `rng(n)` must return a uniformly random integer in `0 .. n`.
+    /// # Complexity
+    /// O(len)
+    pub fn shuffle<R: FnMut(usize) -> usize>(&mut self, mut rng: R) {
+        self.rearrange(|nodes| {
+            for i in (1 .. nodes.len()).rev() {
+                nodes.swap(i, rng(i + 1));
+            }
+        });
+    }
+
+    /// Returns whether the elements are in non-decreasing order, i.e. whether `insert_sorted` can be used.
+    pub fn is_sorted(&self) -> bool where T: PartialOrd {
+        self.is_sorted_by(|a, b| a <= b)
+    }
+
+    /// Returns whether `compare(a, b)` holds for every pair of adjacent elements `a`, `b`.
+    pub fn is_sorted_by<F: FnMut(&T, &T) -> bool>(&self, mut compare: F) -> bool {
+        self.iter().zip(self.iter().skip(1)).all(|(a, b)| compare(a, b))
+    }
+}
+
+/// Only for lists without an aggregate, since a write through the reference would leave the aggregates stale.
+impl<T, B: Balance, O: Observer<T>> TreeList<T, B, (), O> {
+    /// Returns a mutable reference to the element at the given index.
+    /// The observer is notified through `on_set` when the reference is dropped.
+    pub fn get_mut(&mut self, index: usize) -> Option<ElementMut<'_, T, O>> {
+        if index < self.len() {
+            let root = B::access(self.root.take().unwrap(), index);
+            let root = self.root.insert(root);
+            let element = unsafe { root.as_mut().get_unchecked_mut() }.at_mut(index).data_mut();
+            Some(ElementMut::new(element, index, &mut self.observer))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, B: Balance, M: Aggregate<T>, O> Drop for TreeList<T, B, M, O> {
+    /// Frees the nodes without recursion, since some strategies allow deep trees.
+    fn drop(&mut self) {
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.replace_child(false, None));
+            stack.extend(node.replace_child(true, None));
+        }
+    }
+}
+
+impl<T, B: Balance, M: Aggregate<T>, O: Default> Default for TreeList<T, B, M, O> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            free: Vec::new(),
+            state: B::State::default(),
+            observer: O::default(),
+        }
+    }
 }
 
-impl<T> FromIterator<T> for TreeList<T> {
+impl<T, B: Balance, M: Aggregate<T>, O: Default> FromIterator<T> for TreeList<T, B, M, O> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        Self::raw(Node::from_iter(iter))
+        let mut list = Self::default();
+        list.root = Node::from_iter(iter, &mut list.state);
+        list
     }
 }
 
-impl<T> Index<usize> for TreeList<T> {
+impl<T, B: Balance, M: Aggregate<T>, O: Observer<T>> Index<usize> for TreeList<T, B, M, O> {
     type Output = T;
 
     fn index(&self, index: usize) -> &T {
@@ -189,15 +576,18 @@ impl<T> Index<usize> for TreeList<T> {
     }
 }
 
-impl<T> IndexMut<usize> for TreeList<T> {
+/// Only for unobserved lists, since a plain `&mut T` cannot report the write; use `get_mut` otherwise.
+impl<T, B: Balance> IndexMut<usize> for TreeList<T, B> {
     fn index_mut(&mut self, index: usize) -> &mut T {
         assert!(index < self.len());
-        unsafe { self.root.as_mut().unwrap().as_mut().get_unchecked_mut() }.at_mut(index).data_mut()
+        let root = B::access(self.root.take().unwrap(), index);
+        let root = self.root.insert(root);
+        unsafe { root.as_mut().get_unchecked_mut() }.at_mut(index).data_mut()
     }
 }
 
-impl<T: Debug> Debug for TreeList<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<T: Debug, B: Balance, M: Aggregate<T>, O: Observer<T>> Debug for TreeList<T, B, M, O> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.write_str("TreeList {")?;
         let mut first = true;
         for element in self.iter() {
@@ -216,7 +606,7 @@ impl<T: Debug> Debug for TreeList<T> {
 mod tests {
     #[test]
     fn push() {
-        let mut list = super::TreeList::new();
+        let mut list = super::TreeList::<_>::new();
         for &x in &[6, 5, 4] {
             list.push_front(x);
         }
@@ -231,18 +621,17 @@ mod tests {
             res.push(x);
         }
         assert_eq!(res.len(), 10);
-        for i in 0 .. 10 {
-            assert_eq!(res[i], i);
+        for (i, &x) in res.iter().enumerate() {
+            assert_eq!(x, i);
         }
     }
 
     #[test]
     fn insert() {
-        let mut list = super::TreeList::new();
+        let mut list = super::TreeList::<_>::new();
         list.insert(0, 4);
         list.insert(0, 0);
         list.insert(1, 1);
-        eprintln!("{:?}, len={}", list, list.len());
         list.insert(3, 6);
         list.insert(2, 2);
         list.insert(4, 5);
@@ -252,4 +641,87 @@ mod tests {
             assert_eq!(i, x);
         }
     }
+
+    #[test]
+    fn recycle() {
+        let mut list = super::TreeList::<_>::with_capacity(10);
+        assert_eq!(list.capacity(), 10);
+        for i in 0 .. 20 {
+            list.push_back(i);
+        }
+        assert_eq!(list.capacity(), 20);
+        assert_eq!(list.pop_front(), Some(0));
+        assert_eq!(list.remove(5), Some(6));
+        assert_eq!(list.drain(2 .. 5).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(list.drain(..= 1).rev().collect::<Vec<_>>(), vec![2, 1]);
+        list.drain(10 ..);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+        assert_eq!(list.capacity(), 20);
+        list.clear();
+        assert_eq!(list.capacity(), 20);
+        list.reserve(25);
+        assert_eq!(list.capacity(), 25);
+        for i in 0 .. 5 {
+            list.push_front(i);
+        }
+        assert_eq!(list.capacity(), 25);
+        list.shrink_to_fit();
+        assert_eq!(list.capacity(), list.len());
+    }
+
+    #[test]
+    fn move_ranges() {
+        let mut list = (0 .. 10).collect::<super::TreeList<_>>();
+        list.move_range(2 .. 5, 5);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0, 1, 5, 6, 7, 2, 3, 4, 8, 9]);
+        list.move_range(5 ..= 7, 0);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4, 0, 1, 5, 6, 7, 8, 9]);
+        list.swap_ranges(8 .., 0 .. 3);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![8, 9, 0, 1, 5, 6, 7, 2, 3, 4]);
+        list.swap_ranges(2 .. 2, 4 .. 5);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![8, 9, 5, 0, 1, 6, 7, 2, 3, 4]);
+        list.extend_from_within(1 .. 4);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![8, 9, 5, 0, 1, 6, 7, 2, 3, 4, 9, 5, 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlap")]
+    fn swap_overlapping_ranges() {
+        let mut list = (0 .. 10).collect::<super::TreeList<_>>();
+        list.swap_ranges(0 .. 5, 4 .. 6);
+    }
+
+    #[test]
+    fn sort() {
+        let mut seed = 1u64;
+        let mut rng = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        let mut list = (0 .. 1000).map(|i| (i % 7, i)).collect::<super::TreeList<_>>();
+        list.shuffle(&mut rng);
+        assert!(!list.is_sorted());
+        list.sort_by_key(|p| p.1);
+        list.sort_by_key(|p| p.0);
+        assert!(list.is_sorted_by(|a, b| a.0 < b.0 || (a.0 == b.0 && a.1 < b.1)));
+        list.sort_unstable_by(|a, b| b.cmp(a));
+        let mut expected = (0 .. 1000).map(|i| (i % 7, i)).collect::<Vec<_>>();
+        expected.sort_unstable_by(|a, b| b.cmp(a));
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+        list.sort();
+        assert!(list.is_sorted());
+        list.insert_sorted((3, 0));
+        assert!(list.is_sorted());
+    }
+
+    /// Builds the library with `#![no_std]` in a separate target directory.
+    #[test]
+    fn no_std_build() {
+        let status = std::process::Command::new(option_env!("CARGO").unwrap_or("cargo"))
+            .args(["build", "--lib", "--no-default-features", "--target-dir", "target/no_std"])
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
 }
@@ -0,0 +1,347 @@
+use core::{borrow::Borrow, ops::*, iter::{FromIterator, Take}, fmt::Debug};
+
+use super::*;
+
+/// An ordered map that also supports positional access, i.e. rank and select, in O(log(len)).
+/// Entries are kept in a `TreeList<(K, V)>` sorted by key.
+pub struct TreeMap<K, V> {
+    list: TreeList<(K, V)>,
+}
+
+impl<K, V> TreeMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            list: TreeList::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    /// Returns the `index`-th entry in key order.
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        if index < self.len() {
+            let (key, value) = &self.list[index];
+            Some((key, value))
+        } else {
+            None
+        }
+    }
+
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        if index < self.len() {
+            let (key, value) = &mut self.list[index];
+            Some((&*key, value))
+        } else {
+            None
+        }
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.get_index(0)
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.get_index(self.len().wrapping_sub(1))
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            iter: self.list.iter().take(self.len()),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+impl<K: Ord, V> TreeMap<K, V> {
+    /// Returns the number of keys less than `key`.
+    /// # Complexity
+    /// O(log(len))
+    pub fn rank<Q: Ord + ?Sized>(&self, key: &Q) -> usize where K: Borrow<Q> {
+        self.list.leftmost(|(k, _)| k.borrow() >= key).unwrap_or(self.len())
+    }
+
+    /// Returns the position of `key` in key order, if it is present.
+    pub fn index_of_key<Q: Ord + ?Sized>(&self, key: &Q) -> Option<usize> where K: Borrow<Q> {
+        let index = self.rank(key);
+        if index < self.len() && self.list[index].0.borrow() == key {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains_key<Q: Ord + ?Sized>(&self, key: &Q) -> bool where K: Borrow<Q> {
+        self.index_of_key(key).is_some()
+    }
+
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&V> where K: Borrow<Q> {
+        self.index_of_key(key).map(|index| &self.list[index].1)
+    }
+
+    pub fn get_mut<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<&mut V> where K: Borrow<Q> {
+        let index = self.index_of_key(key)?;
+        Some(&mut self.list[index].1)
+    }
+
+    /// Inserts a key-value pair.
+    /// If the key was present, its value is replaced and the old value is returned.
+    /// # Complexity
+    /// O(log(len))
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    /// Removes `key` from the map, returning its value if it was present.
+    /// # Complexity
+    /// O(log(len))
+    pub fn remove<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<V> where K: Borrow<Q> {
+        let index = self.index_of_key(key)?;
+        self.list.remove(index).map(|(_, value)| value)
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let index = self.rank(&key);
+        if index < self.len() && self.list[index].0 == key {
+            Entry::Occupied(OccupiedEntry { map: self, index })
+        } else {
+            Entry::Vacant(VacantEntry { map: self, key, index })
+        }
+    }
+
+    /// Returns an iterator over the entries whose keys are in `range`.
+    /// # Complexity
+    /// O(log(len)) to start
+    pub fn range<Q: Ord + ?Sized, R: RangeBounds<Q>>(&self, range: R) -> Iter<'_, K, V> where K: Borrow<Q> {
+        use Bound::*;
+        let start = match range.start_bound() {
+            Included(key) => self.rank(key),
+            Excluded(key) => self.list.leftmost(|(k, _)| k.borrow() > key).unwrap_or(self.len()),
+            Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Included(key) => self.list.leftmost(|(k, _)| k.borrow() > key).unwrap_or(self.len()),
+            Excluded(key) => self.rank(key),
+            Unbounded => self.len(),
+        };
+        Iter {
+            iter: self.list.iter_from(start).take(end.saturating_sub(start)),
+        }
+    }
+
+    /// Splits the map at `key`, returning the entries whose keys are greater than or equal to `key`.
+    /// # Complexity
+    /// O(log(len))
+    pub fn split_off_key<Q: Ord + ?Sized>(&mut self, key: &Q) -> Self where K: Borrow<Q> {
+        let at = self.rank(key);
+        Self {
+            list: self.list.split_off(at),
+        }
+    }
+}
+
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+pub struct OccupiedEntry<'a, K, V> {
+    map: &'a mut TreeMap<K, V>,
+    index: usize,
+}
+
+pub struct VacantEntry<'a, K, V> {
+    map: &'a mut TreeMap<K, V>,
+    key: K,
+    index: usize,
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns the position the entry has or would have in key order.
+    pub fn index(&self) -> usize {
+        match self {
+            Entry::Occupied(entry) => entry.index(),
+            Entry::Vacant(entry) => entry.index(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V where V: Default {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.map.list[self.index].0
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.list[self.index].1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.list[self.index].1
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.list[self.index].1
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        core::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.list.remove(self.index).unwrap()
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map.list.insert(self.index, (self.key, value));
+        &mut self.map.list[self.index].1
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    iter: Take<iter::Iter<'a, (K, V)>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K, V> Default for TreeMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for TreeMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for TreeMap<K, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("TreeMap ")?;
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TreeMap, Entry};
+
+    #[test]
+    fn map() {
+        let mut map = TreeMap::new();
+        for i in 0 .. 100 {
+            assert_eq!(map.insert(i * 37 % 100, i), None);
+        }
+        assert_eq!(map.insert(74, 1000), Some(2));
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.get(&74), Some(&1000));
+        assert_eq!(map.get_index(74), Some((&74, &1000)));
+        assert_eq!(map.index_of_key(&50), Some(50));
+        assert_eq!(map.remove(&50), Some(50 * 73 % 100));
+        assert_eq!(map.remove(&50), None);
+        assert_eq!(map.index_of_key(&50), None);
+        assert_eq!(map.rank(&50), 50);
+        assert_eq!(map.range(10 .. 15).map(|(&k, _)| k).collect::<Vec<_>>(), vec![10, 11, 12, 13, 14]);
+        assert_eq!(map.range(48 ..= 52).map(|(&k, _)| k).collect::<Vec<_>>(), vec![48, 49, 51, 52]);
+        let right = map.split_off_key(&90);
+        assert_eq!(right.keys().copied().collect::<Vec<_>>(), (90 .. 100).collect::<Vec<_>>());
+        assert_eq!(map.len(), 89);
+    }
+
+    #[test]
+    fn entry() {
+        let mut map = TreeMap::new();
+        for word in "the quick brown fox jumps over the lazy dog the end".split(' ') {
+            *map.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(map.get("the"), Some(&3));
+        assert_eq!(map.index_of_key("fox"), Some(3));
+        match map.entry("zebra") {
+            Entry::Vacant(entry) => assert_eq!(entry.index(), 9),
+            Entry::Occupied(_) => unreachable!(),
+        }
+    }
+}
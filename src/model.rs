@@ -0,0 +1,265 @@
+//! Differential tests: random operation sequences are applied to a `TreeList` and to a `Vec`,
+//! and the two are compared after every step.
+//! A failing sequence is minimized by dropping operations before it is reported.
+
+use std::{panic::{catch_unwind, AssertUnwindSafe}, vec::Vec, string::String, format};
+
+use crate::{TreeList, node::Node, balance::*};
+
+/// xorshift64*, seeded per test so failures are reproducible.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// Positions are stored unresolved and taken modulo the current length when applied,
+/// so any subsequence of operations is still valid.
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(usize, u32),
+    Remove(usize),
+    PushFront(u32),
+    PushBack(u32),
+    PopFront,
+    PopBack,
+    /// Moves the tail starting at the position to the second list.
+    SplitOff(usize),
+    /// Moves the second list to the end.
+    Append,
+    Splice(usize, usize, Vec<u32>),
+    Reverse,
+    InsertSorted(u32),
+    ExtendFromWithin(usize, usize),
+}
+
+fn random_op(rng: &mut Rng, sorted: bool) -> Op {
+    let value = rng.below(1000) as u32;
+    let at = rng.next() as usize;
+    if sorted {
+        return match rng.below(4) {
+            0 => Op::Remove(at),
+            1 => Op::PopFront,
+            2 => Op::PopBack,
+            _ => Op::InsertSorted(value),
+        };
+    }
+    match rng.below(11) {
+        0 => Op::Insert(at, value),
+        1 => Op::Remove(at),
+        2 => Op::PushFront(value),
+        3 => Op::PushBack(value),
+        4 => Op::PopFront,
+        5 => Op::PopBack,
+        6 => Op::SplitOff(at),
+        7 => Op::Append,
+        8 => {
+            let replace_with = (0 .. rng.below(4)).map(|_| rng.below(1000) as u32).collect();
+            Op::Splice(at, rng.next() as usize, replace_with)
+        }
+        9 => Op::Reverse,
+        _ => Op::ExtendFromWithin(at, rng.next() as usize),
+    }
+}
+
+/// Checks the lengths and balance of every node; `tag_ok` checks the strategy's tag.
+fn check_tree<B: Balance>(node: Option<&Node<u32, B>>, tag_ok: fn(&Node<u32, B>) -> bool) -> Result<(), String> {
+    let mut stack: Vec<_> = node.into_iter().collect();
+    while let Some(node) = stack.pop() {
+        let left = node.child(false);
+        let right = node.child(true);
+        if node.len() != 1 + node.child_len(false) + node.child_len(true) {
+            return Err(format!("len of node {} is {}", node.data(), node.len()));
+        }
+        if B::heavy_side(left, right).is_some() {
+            return Err(format!("node {} is unbalanced", node.data()));
+        }
+        if !tag_ok(node) {
+            return Err(format!("tag of node {} is inconsistent", node.data()));
+        }
+        stack.extend(left);
+        stack.extend(right);
+    }
+    Ok(())
+}
+
+fn compare<B: Balance>(list: &TreeList<u32, B>, expected: &[u32], tag_ok: fn(&Node<u32, B>) -> bool) -> Result<(), String> {
+    if list.len() != expected.len() {
+        return Err(format!("len is {}, expected {}", list.len(), expected.len()));
+    }
+    let actual = list.iter().copied().collect::<Vec<_>>();
+    if actual != expected {
+        return Err(format!("contents are {:?}, expected {:?}", actual, expected));
+    }
+    check_tree(list.root.as_deref(), tag_ok)
+}
+
+fn execute<B: Balance>(ops: &[Op], tag_ok: fn(&Node<u32, B>) -> bool) -> Result<(), String> {
+    let mut list = TreeList::<u32, B>::default();
+    let mut other = TreeList::<u32, B>::default();
+    let mut expected = vec![];
+    let mut expected_other = vec![];
+    for (step, op) in ops.iter().enumerate() {
+        let len = expected.len();
+        match op {
+            &Op::Insert(at, value) => {
+                list.insert(at % (len + 1), value);
+                expected.insert(at % (len + 1), value);
+            }
+            &Op::Remove(at) if len > 0 => {
+                let (actual, value) = (list.remove(at % len), expected.remove(at % len));
+                if actual != Some(value) {
+                    return Err(format!("step {}: removed {:?}, expected {}", step, actual, value));
+                }
+            }
+            Op::Remove(_) => {}
+            &Op::PushFront(value) => {
+                list.push_front(value);
+                expected.insert(0, value);
+            }
+            &Op::PushBack(value) => {
+                list.push_back(value);
+                expected.push(value);
+            }
+            Op::PopFront => {
+                let value = if expected.is_empty() { None } else { Some(expected.remove(0)) };
+                if list.pop_front() != value {
+                    return Err(format!("step {}: pop_front mismatch", step));
+                }
+            }
+            Op::PopBack => {
+                if list.pop_back() != expected.pop() {
+                    return Err(format!("step {}: pop_back mismatch", step));
+                }
+            }
+            &Op::SplitOff(at) => {
+                other = list.split_off(at % (len + 1));
+                expected_other = expected.split_off(at % (len + 1));
+            }
+            Op::Append => {
+                list.append(&mut other);
+                expected.append(&mut expected_other);
+                compare(&other, &expected_other, tag_ok).map_err(|e| format!("step {}: other: {}", step, e))?;
+            }
+            Op::Splice(a, b, replace_with) => {
+                let (mut l, mut r) = (a % (len + 1), b % (len + 1));
+                if l > r {
+                    core::mem::swap(&mut l, &mut r);
+                }
+                let removed = list.splice(l .. r, replace_with.iter().copied());
+                let expected_removed = expected.splice(l .. r, replace_with.iter().copied()).collect::<Vec<_>>();
+                compare(&removed, &expected_removed, tag_ok).map_err(|e| format!("step {}: removed: {}", step, e))?;
+            }
+            &Op::ExtendFromWithin(a, b) => {
+                let (l, r) = (a % (len + 1), b % (len + 1));
+                let range = l.min(r) .. l.max(r);
+                list.extend_from_within(range.clone());
+                expected.extend_from_within(range);
+            }
+            Op::Reverse => {
+                list.reverse();
+                expected.reverse();
+            }
+            &Op::InsertSorted(value) => {
+                list.insert_sorted(value);
+                let at = expected.partition_point(|&x| x < value);
+                expected.insert(at, value);
+            }
+        }
+        compare(&list, &expected, tag_ok).map_err(|e| format!("step {} ({:?}): {}", step, op, e))?;
+    }
+    Ok(())
+}
+
+fn failure<B: Balance>(ops: &[Op], tag_ok: fn(&Node<u32, B>) -> bool) -> Option<String> {
+    match catch_unwind(AssertUnwindSafe(|| execute(ops, tag_ok))) {
+        Ok(result) => result.err(),
+        Err(_) => Some(String::from("panicked")),
+    }
+}
+
+/// Runs `steps` random operations and panics with a minimized sequence if they disagree with `Vec`.
+fn run<B: Balance>(seed: u64, steps: usize, sorted: bool, tag_ok: fn(&Node<u32, B>) -> bool) {
+    let mut rng = Rng::new(seed);
+    let mut ops = (0 .. steps).map(|_| random_op(&mut rng, sorted)).collect::<Vec<_>>();
+    if failure(&ops, tag_ok).is_none() {
+        return;
+    }
+    let mut shrunk = true;
+    while shrunk {
+        shrunk = false;
+        let mut i = ops.len();
+        while i > 0 {
+            i -= 1;
+            let op = ops.remove(i);
+            if failure(&ops, tag_ok).is_none() {
+                ops.insert(i, op);
+            } else {
+                shrunk = true;
+            }
+        }
+    }
+    panic!("seed {}: {}\nminimized ops: {:?}", seed, failure(&ops, tag_ok).unwrap(), ops);
+}
+
+fn avl_tag(node: &Node<u32, Avl>) -> bool {
+    let height = |dir| node.child(dir).map(|child| *child.tag()).unwrap_or(0);
+    *node.tag() == height(false).max(height(true)) + 1
+}
+
+fn treap_tag(node: &Node<u32, Treap>) -> bool {
+    [false, true].iter().all(|&dir| node.child(dir).map(|child| child.tag() <= node.tag()).unwrap_or(true))
+}
+
+fn any_tag<B: Balance>(_: &Node<u32, B>) -> bool {
+    true
+}
+
+fn run_all<B: Balance>(tag_ok: fn(&Node<u32, B>) -> bool) {
+    for seed in 0 .. 20 {
+        run::<B>(seed, 300, false, tag_ok);
+        run::<B>(seed, 300, true, tag_ok);
+    }
+}
+
+#[test]
+fn avl() {
+    run_all::<Avl>(avl_tag);
+}
+
+#[test]
+fn treap() {
+    run_all::<Treap>(treap_tag);
+}
+
+#[test]
+fn weight_balanced() {
+    run_all::<WeightBalanced>(any_tag);
+}
+
+#[test]
+fn splay() {
+    run_all::<Splay>(any_tag);
+}
+
+#[test]
+#[should_panic(expected = "minimized ops")]
+fn reports_minimized_sequence() {
+    fn broken(node: &Node<u32, Avl>) -> bool {
+        node.len() < 5
+    }
+    run::<Avl>(0, 100, false, broken);
+}
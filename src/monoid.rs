@@ -0,0 +1,52 @@
+//! Associative operations with an identity, used for subtree aggregates.
+
+use core::{marker::PhantomData, ops::Add};
+
+pub trait Monoid {
+    type Value: Clone;
+
+    fn identity() -> Self::Value;
+
+    /// Combines two values; must be associative.
+    fn op(a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+/// A monoid over the elements of a list, whose value is kept for every subtree of a `TreeList<T, B, M>`.
+/// The value of a range is the product of `lift` of its elements in order.
+pub trait Aggregate<T>: Monoid {
+    fn lift(element: &T) -> Self::Value;
+}
+
+/// No aggregate.
+impl Monoid for () {
+    type Value = ();
+
+    fn identity() {}
+
+    fn op(_: &(), _: &()) {}
+}
+
+impl<T> Aggregate<T> for () {
+    fn lift(_: &T) {}
+}
+
+/// Sum with `Default::default()` as zero.
+pub struct Additive<T>(PhantomData<T>);
+
+impl<T: Clone + Default + Add<Output = T>> Monoid for Additive<T> {
+    type Value = T;
+
+    fn identity() -> T {
+        T::default()
+    }
+
+    fn op(a: &T, b: &T) -> T {
+        a.clone() + b.clone()
+    }
+}
+
+impl<T: Clone + Default + Add<Output = T>> Aggregate<T> for Additive<T> {
+    fn lift(element: &T) -> T {
+        element.clone()
+    }
+}
@@ -1,40 +1,115 @@
-use std::{pin::Pin};
+use core::{convert::TryFrom, pin::Pin, mem::MaybeUninit, ptr::{self, addr_of, addr_of_mut}};
+use alloc::{boxed::Box, vec, vec::Vec};
 
-pub type PinnedNode<T> = Pin<Box<Node<T>>>;
+use crate::{balance::{Balance, Avl}, monoid::Aggregate, tree::{self, BinaryNode, Link}};
 
-pub struct Node<T> {
+pub type PinnedNode<T, B = Avl, M = ()> = Pin<Box<Node<T, B, M>>>;
+pub type Tree<T, B = Avl, M = ()> = Option<PinnedNode<T, B, M>>;
+/// An allocation for a node that holds no value, kept for reuse.
+pub type FreeNode<T, B = Avl, M = ()> = Box<MaybeUninit<Node<T, B, M>>>;
+
+/// Unsigned integer type that stores the length of a subtree, chosen by `Balance::Size`.
+/// A narrower type makes nodes smaller but limits the length of a list.
+pub trait Size: Copy + Send + Sync {
+    /// Converts a length, panicking if it does not fit.
+    fn from_usize(len: usize) -> Self;
+
+    fn to_usize(self) -> usize;
+}
+
+macro_rules! impl_size {
+    ($($t:ty),*) => {$(
+        impl Size for $t {
+            fn from_usize(len: usize) -> Self {
+                match <$t>::try_from(len) {
+                    Ok(len) => len,
+                    Err(_) => panic!("length {} overflows the index type {}", len, stringify!($t)),
+                }
+            }
+
+            fn to_usize(self) -> usize {
+                self as usize
+            }
+        }
+    )*};
+}
+
+impl_size!(u8, u16, u32, usize);
+
+/// A node of `TreeList`.
+///
+/// Besides `data`, a node holds two child pointers, its length (`B::Size`), `B::Tag`
+/// and the aggregate `M::Value` of its subtree, which takes no space for the default `M = ()`.
+/// On 64-bit targets with `Avl` (`usize` length, `u8` height), `Node<u32>` and `Node<u64>` are 32 and 40 bytes;
+/// with `Avl<u32>`, `Node<u8, Avl<u32>>` and `Node<u64, Avl<u32>>` are 24 and 32 bytes. These are asserted below.
+pub struct Node<T, B: Balance = Avl, M: Aggregate<T> = ()> {
     data: T,
-    len: usize,
-    height: usize,
+    len: B::Size,
+    tag: B::Tag,
+    sum: M::Value,
     // parent: Option<(bool, NonNull<Node<T>>)>,
-    children: [Option<PinnedNode<T>>; 2]
+    children: [Option<PinnedNode<T, B, M>>; 2]
 }
 
-impl<T> Node<T> {
-    pub fn new(data: T) -> Self {
+#[cfg(target_pointer_width = "64")]
+const _: () = {
+    use core::mem::size_of;
+    assert!(size_of::<Node<u32>>() == 32);
+    assert!(size_of::<Node<u64>>() == 40);
+    assert!(size_of::<Node<u8, Avl<u32>>>() == 24);
+    assert!(size_of::<Node<u64, Avl<u32>>>() == 32);
+};
+
+impl<T, B: Balance, M: Aggregate<T>> Node<T, B, M> {
+    /// Creates a single node, drawing its tag from the list's `state`.
+    pub fn new(data: T, state: &mut B::State) -> Self {
         Node {
+            sum: M::lift(&data),
             data,
-            len: 1,
-            height: 1,
+            len: B::Size::from_usize(1),
+            tag: B::tag(state),
             // parent: None,
             children: [None, None]
         }
     }
 
-    pub fn from_iter<I: IntoIterator<Item = T>>(data: I) -> Option<Pin<Box<Self>>> {
+    pub fn from_iter<I: IntoIterator<Item = T>>(data: I, state: &mut B::State) -> Option<Pin<Box<Self>>> {
         let mut node = None;
         for element in data {
-            node = Node::merge(node, Some(Node::pin(element)));
+            node = Node::merge(node, Some(Node::pin(element, state)));
         }
         node
     }
 
-    pub fn pin(data: T) -> Pin<Box<Self>> {
-        Box::pin(Self::new(data))
+    pub fn pin(data: T, state: &mut B::State) -> Pin<Box<Self>> {
+        Box::pin(Self::new(data, state))
+    }
+
+    /// Builds a single node in a recycled allocation.
+    pub fn pin_in(free: FreeNode<T, B, M>, data: T, state: &mut B::State) -> Pin<Box<Self>> {
+        let raw = Box::into_raw(free) as *mut Self;
+        unsafe {
+            raw.write(Self::new(data, state));
+            Box::into_pin(Box::from_raw(raw))
+        }
     }
 
+    /// Moves the data out of a single node and returns its allocation for reuse.
+    pub fn recycle(self: Pin<Box<Self>>) -> (T, FreeNode<T, B, M>) {
+        debug_assert!(self.len() == 1);
+        let raw = Box::into_raw(unsafe { Pin::into_inner_unchecked(self) });
+        unsafe {
+            let data = ptr::read(addr_of!((*raw).data));
+            ptr::drop_in_place(addr_of_mut!((*raw).tag));
+            ptr::drop_in_place(addr_of_mut!((*raw).sum));
+            ptr::drop_in_place(addr_of_mut!((*raw).children));
+            (data, Box::from_raw(raw as *mut MaybeUninit<Self>))
+        }
+    }
+
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
-        self.len
+        self.len.to_usize()
     }
 
     pub fn data(&self) -> &T {
@@ -49,7 +124,21 @@ impl<T> Node<T> {
         self.data
     }
 
-    pub fn child(&self, dir: bool) -> Option<&Node<T>> {
+    /// Balancing state of the node, maintained by `B`.
+    pub fn tag(&self) -> &B::Tag {
+        &self.tag
+    }
+
+    pub fn tag_mut(&mut self) -> &mut B::Tag {
+        &mut self.tag
+    }
+
+    /// Aggregate of the elements in the subtree.
+    pub fn sum(&self) -> &M::Value {
+        &self.sum
+    }
+
+    pub fn child(&self, dir: bool) -> Option<&Self> {
         if let Some(child) = &self.children[dir as usize] {
             Some(child.as_ref().get_ref())
         } else {
@@ -57,60 +146,53 @@ impl<T> Node<T> {
         }
     }
 
+    /// Borrows the left child, the data and the right child at the same time.
+    pub fn parts_mut(&mut self) -> (Option<&mut Self>, &mut T, Option<&mut Self>) {
+        let [left, right] = &mut self.children;
+        let left = left.as_mut().map(|node| unsafe { node.as_mut().get_unchecked_mut() });
+        let right = right.as_mut().map(|node| unsafe { node.as_mut().get_unchecked_mut() });
+        (left, &mut self.data, right)
+    }
+
+    pub fn child_len(&self, dir: bool) -> usize {
+        self.children[dir as usize].as_ref().map(|child| child.len()).unwrap_or(0)
+    }
+
     pub fn replace_child(self: &mut Pin<Box<Self>>, dir: bool, replacement: Option<Pin<Box<Self>>>) -> Option<Pin<Box<Self>>> {
         let self_mut = unsafe { self.as_mut().get_unchecked_mut() };
-        std::mem::replace(&mut self_mut.children[dir as usize], replacement)
+        core::mem::replace(&mut self_mut.children[dir as usize], replacement)
+    }
+
+    /// Takes both children, returning `(left, self, right)` with `self` as a single node.
+    #[allow(clippy::type_complexity)]
+    pub fn detach(self: Pin<Box<Self>>) -> (Tree<T, B, M>, PinnedNode<T, B, M>, Tree<T, B, M>) {
+        tree::detach(self)
     }
 
     pub fn leftmost<P: Fn(&Self) -> bool>(&self, predicate: P) -> Option<usize> {
-        let left_len = self.children[0].as_ref().map(|n| n.len()).unwrap_or(0);
-        if predicate(&self) {
-            if let Some(left) = &self.children[0] {
-                return left.leftmost(predicate).or(Some(left_len));
-            } else {
-                return Some(left_len);
-            }
-        }
-        Some(left_len + 1 + self.children[1].as_ref()?.leftmost(predicate)?)
+        tree::leftmost(self, predicate)
     }
 
     pub fn rightmost<P: Fn(&Self) -> bool>(&self, predicate: P) -> Option<usize> {
-        let left_len = self.children[0].as_ref().map(|n| n.len()).unwrap_or(0);
-        let result = predicate(&self);
-        if result {
-            if let Some(right) = &self.children[1] {
-                return right.rightmost(predicate).map(|i| left_len + 1 + i).or(Some(left_len));
-            }
-            return Some(left_len);
-        }
-        if let Some(left) = &self.children[0] {
-            return left.rightmost(predicate);
-        }
-        None
+        tree::rightmost(self, predicate)
     }
 
     pub fn at(&self, index: usize) -> &Self {
-        let mut left_len = 0;
-        if let Some(left) = &self.children[0] {
-            left_len = left.len();
-            if index < left_len {
-                return left.at(index);
-            }
-        }
-        if index == left_len {
-            return self;
-        }
-        self.children[1].as_ref().unwrap().at(index - left_len - 1)
+        tree::at(self, index)
     }
 
-    pub fn at_mut(&mut self, index: usize) -> &mut Self {
-        let left_len = self.children[0].as_ref().map(|node| node.len()).unwrap_or(0);
-        if index < left_len {
-            unsafe { self.children[0].as_mut().unwrap().as_mut().get_unchecked_mut() }.at_mut(index)
-        } else if index == left_len {
-            self
-        } else {
-            unsafe { self.children[1].as_mut().unwrap().as_mut().get_unchecked_mut() }.at_mut(index - left_len - 1)
+    pub fn at_mut(&mut self, mut index: usize) -> &mut Self {
+        let mut node = self;
+        loop {
+            let left_len = node.child_len(false);
+            if index < left_len {
+                node = unsafe { node.children[0].as_mut().unwrap().as_mut().get_unchecked_mut() };
+            } else if index == left_len {
+                return node;
+            } else {
+                index -= left_len + 1;
+                node = unsafe { node.children[1].as_mut().unwrap().as_mut().get_unchecked_mut() };
+            }
         }
     }
 
@@ -122,49 +204,97 @@ impl<T> Node<T> {
     //     }
     // }
 
-    pub fn rotate(mut self: Pin<Box<Self>>, dir: bool) -> Pin<Box<Self>> {
-        let self_mut = unsafe { self.as_mut().get_unchecked_mut() };
-        if let Some(mut child) = self_mut.children[!dir as usize].take() {
-            let child_mut = unsafe { child.as_mut().get_unchecked_mut() };
-            self_mut.children[!dir as usize] = child_mut.children[dir as usize].take();
-            self_mut.update();
-            child_mut.children[dir as usize] = Some(self);
-            child_mut.update();
-            child
-        } else {
-            self
-        }
+    pub fn rotate(self: Pin<Box<Self>>, dir: bool) -> Pin<Box<Self>> {
+        tree::rotate(self, dir)
     }
 
     pub fn balance(self: Pin<Box<Self>>) -> Pin<Box<Self>> {
-        let lh = self.children[0].as_ref().map(|child| child.height).unwrap_or(0);
-        let rh = self.children[1].as_ref().map(|child| child.height).unwrap_or(0);
-        if lh + 2 < rh {
-            self.rotate(false)
-        } else if rh + 2 < lh {
-            self.rotate(true)
-        } else {
-            self
-        }
+        B::balance(self)
     }
 
     pub fn update(&mut self) {
         let mut len = 1;
-        let mut height = 1;
-        for child in &self.children {
-            if let Some(child) = child {
-                len += child.len;
-                height = height.max(child.height + 1);
+        let mut sum = M::lift(&self.data);
+        if let Some(left) = &self.children[0] {
+            len += left.len();
+            sum = M::op(&left.sum, &sum);
+        }
+        if let Some(right) = &self.children[1] {
+            len += right.len();
+            sum = M::op(&sum, &right.sum);
+        }
+        self.len = B::Size::from_usize(len);
+        self.sum = sum;
+        B::update(self);
+    }
+
+    /// Reverses the order of the subtree by rebuilding it from its nodes, so that the aggregates are recomputed.
+    /// # Complexity
+    /// O(len)
+    pub fn reverse(self: Pin<Box<Self>>) -> Pin<Box<Self>> {
+        let mut nodes = self.into_nodes();
+        nodes.reverse();
+        Self::build(nodes).unwrap()
+    }
+
+    /// Returns the aggregate of the elements in `l .. r` of the subtree.
+    /// # Complexity
+    /// O(depth)
+    pub fn fold(&self, mut l: usize, mut r: usize) -> M::Value {
+        assert!(l <= r && r <= self.len());
+        if l == r {
+            return M::identity();
+        }
+        // Descend to the highest node inside the range; the range is then split by it.
+        let mut node = self;
+        loop {
+            let left_len = node.child_len(false);
+            if r <= left_len {
+                node = node.child(false).unwrap();
+            } else if l > left_len {
+                l -= left_len + 1;
+                r -= left_len + 1;
+                node = node.child(true).unwrap();
+            } else {
+                break;
             }
         }
-        self.len = len;
-        self.height = height;
+        // Suffix of the left subtree from `l`, collected from the right.
+        let mut left = M::identity();
+        let mut current = node.child(false);
+        while let Some(n) = current {
+            let left_len = n.child_len(false);
+            if l <= left_len {
+                let right = n.child(true).map(|c| c.sum.clone()).unwrap_or_else(M::identity);
+                left = M::op(&M::op(&M::lift(&n.data), &right), &left);
+                current = if l == left_len { None } else { n.child(false) };
+            } else {
+                l -= left_len + 1;
+                current = n.child(true);
+            }
+        }
+        // Prefix of the right subtree up to `r`, collected from the left.
+        let mut right = M::identity();
+        let mut r = r - node.child_len(false) - 1;
+        let mut current = node.child(true);
+        while let Some(n) = current {
+            let left_len = n.child_len(false);
+            if r > left_len {
+                let before = n.child(false).map(|c| c.sum.clone()).unwrap_or_else(M::identity);
+                right = M::op(&right, &M::op(&before, &M::lift(&n.data)));
+                r -= left_len + 1;
+                current = if r == 0 { None } else { n.child(true) };
+            } else {
+                current = n.child(false);
+            }
+        }
+        M::op(&M::op(&left, &M::lift(&node.data)), &right)
     }
 
     pub fn merge(left: Option<Pin<Box<Self>>>, right: Option<Pin<Box<Self>>>) -> Option<Pin<Box<Self>>> {
         if let Some(left) = left {
             if let Some(right) = right {
-                Some(left.append(right))
+                Some(B::merge(left, right))
             } else {
                 Some(left)
             }
@@ -173,43 +303,119 @@ impl<T> Node<T> {
         }
     }
 
-    pub fn append(mut self: Pin<Box<Self>>, other: Pin<Box<Self>>) -> Pin<Box<Self>> {
-        let self_mut = unsafe { self.as_mut().get_unchecked_mut() };
-        let right = &mut self_mut.children[1];
-        *right = Some(if let Some(child) = right.take() {
-            child.append(other)
-        } else {
-            other
-        });
-        self_mut.update();
-        self.balance()
+    /// Joins `left`, `pivot` and `right` in this order, where `pivot` is a single node, as `B` does it.
+    pub fn join(left: Tree<T, B, M>, pivot: Pin<Box<Self>>, right: Tree<T, B, M>) -> Pin<Box<Self>> {
+        B::join(left, pivot, right)
+    }
+
+    /// Joins `left`, `pivot` and `right` in this order, where `pivot` is a single node,
+    /// by descending the spine of the side that `B::heavy_side` calls heavier.
+    pub fn join_spine(left: Tree<T, B, M>, pivot: Pin<Box<Self>>, right: Tree<T, B, M>) -> Pin<Box<Self>> {
+        tree::join_spine(left, pivot, right)
+    }
+
+    /// Builds a tree from single nodes in O(nodes.len()), keeping their order.
+    pub fn build(nodes: Vec<Pin<Box<Self>>>) -> Tree<T, B, M> {
+        B::build(nodes)
+    }
+
+    /// Builds a perfectly balanced tree by making the middle node the root of each subtree.
+    pub fn build_balanced(nodes: Vec<Pin<Box<Self>>>) -> Tree<T, B, M> {
+        fn build<T, B: Balance, M: Aggregate<T>, I: Iterator<Item = PinnedNode<T, B, M>>>(nodes: &mut I, len: usize) -> Tree<T, B, M> {
+            if len == 0 {
+                return None;
+            }
+            let left = build(nodes, len / 2);
+            let mut root = nodes.next().unwrap();
+            let right = build(nodes, len - len / 2 - 1);
+            root.replace_child(false, left);
+            root.replace_child(true, right);
+            unsafe { root.as_mut().get_unchecked_mut() }.update();
+            Some(root)
+        }
+        let len = nodes.len();
+        build(&mut nodes.into_iter(), len)
     }
 
-    pub fn split_at(mut self: Pin<Box<Self>>, at: usize) -> (Option<Pin<Box<Self>>>, Option<Pin<Box<Self>>>) {
-        assert!(at <= self.len);
+    /// Takes the tree apart into single nodes in order. The inverse of `build`.
+    pub fn into_nodes(self: Pin<Box<Self>>) -> Vec<Pin<Box<Self>>> {
+        let mut nodes = Vec::with_capacity(self.len());
+        let mut stack = vec![];
+        let mut next = Some(self);
+        loop {
+            while let Some(mut node) = next {
+                next = node.replace_child(false, None);
+                stack.push(node);
+            }
+            match stack.pop() {
+                Some(mut node) => {
+                    next = node.replace_child(true, None);
+                    unsafe { node.as_mut().get_unchecked_mut() }.update();
+                    nodes.push(node);
+                }
+                None => return nodes,
+            }
+        }
+    }
+
+    /// Concatenates by joining with the first element of `other` as the pivot.
+    pub fn append(self: Pin<Box<Self>>, other: Pin<Box<Self>>) -> Pin<Box<Self>> {
+        tree::append(self, other)
+    }
+
+    pub fn split_at(self: Pin<Box<Self>>, at: usize) -> (Tree<T, B, M>, Tree<T, B, M>) {
+        assert!(at <= self.len());
         if at == 0 {
             (None, Some(self))
-        } else if at == self.len {
+        } else if at == self.len() {
             (Some(self), None)
         } else {
-            let mut left_len = 0;
-            if let Some(left_child) = &self.children[0] {
-                left_len = left_child.len;
-                let self_mut = unsafe { self.as_mut().get_unchecked_mut() };
-                if at <= left_len {
-                    let (left, right) = self_mut.children[0].take().unwrap().split_at(at);
-                    self_mut.children[0] = right;
-                    self_mut.update();
-                    return (left, Some(self.balance()));
-                }
-            }
-            let self_mut = unsafe { self.as_mut().get_unchecked_mut() };
-            let (left, right) = self_mut.children[1].take().unwrap().split_at(at - left_len - 1);
-            self_mut.children[1] = left;
-            self_mut.update();
-            (Some(self.balance()), right)
+            B::split_at(self, at)
         }
     }
+
+    /// Splits by descending to the split point and joining the pieces on the way back up.
+    pub fn split_at_recursive(self: Pin<Box<Self>>, at: usize) -> (Tree<T, B, M>, Tree<T, B, M>) {
+        tree::split_at(self, at)
+    }
+}
+
+impl<T, B: Balance, M: Aggregate<T>> BinaryNode for Node<T, B, M> {
+    fn len(&self) -> usize {
+        Node::len(self)
+    }
+
+    fn child(&self, dir: bool) -> Option<&Self> {
+        Node::child(self, dir)
+    }
+}
+
+impl<T, B: Balance, M: Aggregate<T>> Link for PinnedNode<T, B, M> {
+    type Node = Node<T, B, M>;
+
+    fn node(&self) -> &Node<T, B, M> {
+        self
+    }
+
+    fn replace_child(&mut self, dir: bool, replacement: Option<Self>) -> Option<Self> {
+        Node::replace_child(self, dir, replacement)
+    }
+
+    fn update(&mut self) {
+        unsafe { self.as_mut().get_unchecked_mut() }.update();
+    }
+
+    fn heavy_side(left: Option<&Node<T, B, M>>, right: Option<&Node<T, B, M>>) -> Option<bool> {
+        B::heavy_side(left, right)
+    }
+
+    fn balance(self) -> Self {
+        B::balance(self)
+    }
+
+    fn join(left: Option<Self>, pivot: Self, right: Option<Self>) -> Self {
+        B::join(left, pivot, right)
+    }
 }
 
 // impl<T> Drop for Node<T> {
@@ -219,3 +425,51 @@ impl<T> Node<T> {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::Node;
+    use crate::{balance::Splay, monoid::Additive, TreeList};
+
+    fn depth<T>(node: Option<&Node<T>>) -> usize {
+        node.map(|node| 1 + depth(node.child(false)).max(depth(node.child(true)))).unwrap_or(0)
+    }
+
+    fn height<T>(list: &TreeList<T>) -> usize {
+        depth(list.root.as_deref())
+    }
+
+    /// An AVL tree with n nodes is at most about 1.44 log2(n) high.
+    #[test]
+    fn avl_height() {
+        let bound = |n: usize| (1.45 * (n as f64 + 2.0).log2()) as usize;
+        let (mut front, mut back, mut middle) = (TreeList::new(), TreeList::new(), TreeList::new());
+        for i in 0 .. 100000 {
+            front.push_front(i);
+            back.push_back(i);
+            middle.insert(middle.len() / 2, i);
+        }
+        for list in [&front, &back, &middle] {
+            assert!(height(list) <= bound(list.len()));
+        }
+        let mut right = middle.split_off(12345);
+        assert!(height(&middle) <= bound(middle.len()) && height(&right) <= bound(right.len()));
+        right.append(&mut front);
+        assert!(height(&right) <= bound(right.len()));
+    }
+
+    #[test]
+    fn fold() {
+        let mut list = (0 .. 300u64).collect::<TreeList<_, Splay, Additive<u64>>>();
+        list.reverse();
+        for _ in 0 .. 100 {
+            list.access(list.len() / 3);
+        }
+        let expected = list.iter().copied().collect::<Vec<_>>();
+        for l in 0 ..= expected.len() {
+            for r in (l ..= expected.len()).step_by(7) {
+                assert_eq!(list.fold(l .. r), expected[l .. r].iter().sum::<u64>());
+            }
+        }
+    }
+}
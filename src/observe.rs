@@ -0,0 +1,217 @@
+//! Notifications of the changes made to a `TreeList`.
+//!
+//! An observer is stored in the list as its third type parameter, which defaults to `()`.
+//! The methods of `()` are empty, so an unobserved list pays nothing for the calls.
+
+use core::ops::{Deref, DerefMut, Range};
+
+/// Receives the changes made by the mutating methods of `TreeList<T, B, M, O>`.
+/// Positions of removed or replaced elements refer to the list before the change,
+/// positions of inserted or moved elements to the list after it; each method says which.
+/// Every method does nothing by default.
+/// `IndexMut` is only implemented for unobserved lists, so every write goes through `get_mut` and is reported.
+pub trait Observer<T> {
+    /// `element` was inserted and is now at `index`.
+    fn on_insert(&mut self, _index: usize, _element: &T) {}
+
+    /// `element` was removed from `index`, its position before the removal.
+    fn on_remove(&mut self, _index: usize, _element: &T) {}
+
+    /// The elements in `range` of the list before the change were replaced with `new_len` elements,
+    /// which now start at `range.start`.
+    fn on_splice(&mut self, _range: Range<usize>, _new_len: usize) {}
+
+    /// The elements in `range` of the list before the change were moved so that they now start at `dest`.
+    fn on_move(&mut self, _range: Range<usize>, _dest: usize) {}
+
+    /// The element at `index` was borrowed through `get_mut` and may have changed; `element` is its new value.
+    fn on_set(&mut self, _index: usize, _element: &T) {}
+
+    /// The list was reversed.
+    fn on_reverse(&mut self) {}
+
+    /// The elements were permuted by sorting or shuffling.
+    fn on_reorder(&mut self) {}
+
+    /// The elements from `at` to the end were moved out of the list.
+    fn on_split(&mut self, _at: usize) {}
+
+    /// `len` elements were appended at `index`, the previous length.
+    fn on_append(&mut self, _index: usize, _len: usize) {}
+}
+
+/// No observer.
+impl<T> Observer<T> for () {}
+
+/// A mutable reference to an element returned by `TreeList::get_mut`.
+/// Dropping it reports the element to `Observer::on_set`.
+pub struct ElementMut<'a, T, O: Observer<T>> {
+    element: &'a mut T,
+    index: usize,
+    observer: &'a mut O,
+}
+
+impl<'a, T, O: Observer<T>> ElementMut<'a, T, O> {
+    pub(crate) fn new(element: &'a mut T, index: usize, observer: &'a mut O) -> Self {
+        Self { element, index, observer }
+    }
+}
+
+impl<'a, T, O: Observer<T>> Deref for ElementMut<'a, T, O> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.element
+    }
+}
+
+impl<'a, T, O: Observer<T>> DerefMut for ElementMut<'a, T, O> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.element
+    }
+}
+
+impl<'a, T, O: Observer<T>> Drop for ElementMut<'a, T, O> {
+    fn drop(&mut self) {
+        self.observer.on_set(self.index, self.element);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TreeList;
+    use alloc::vec::Vec;
+
+    #[derive(Default)]
+    struct Recorder(Vec<Event>);
+
+    #[derive(Debug)]
+    enum Event {
+        Insert(usize, u32),
+        Remove(usize, u32),
+        Splice(Range<usize>, usize),
+        Move(Range<usize>, usize),
+        Set(usize, u32),
+        Reverse,
+        Reorder,
+        Split(usize),
+        Append(usize, usize),
+    }
+
+    impl Observer<u32> for Recorder {
+        fn on_insert(&mut self, index: usize, element: &u32) {
+            self.0.push(Event::Insert(index, *element));
+        }
+
+        fn on_remove(&mut self, index: usize, element: &u32) {
+            self.0.push(Event::Remove(index, *element));
+        }
+
+        fn on_splice(&mut self, range: Range<usize>, new_len: usize) {
+            self.0.push(Event::Splice(range, new_len));
+        }
+
+        fn on_move(&mut self, range: Range<usize>, dest: usize) {
+            self.0.push(Event::Move(range, dest));
+        }
+
+        fn on_set(&mut self, index: usize, element: &u32) {
+            self.0.push(Event::Set(index, *element));
+        }
+
+        fn on_reverse(&mut self) {
+            self.0.push(Event::Reverse);
+        }
+
+        fn on_reorder(&mut self) {
+            self.0.push(Event::Reorder);
+        }
+
+        fn on_split(&mut self, at: usize) {
+            self.0.push(Event::Split(at));
+        }
+
+        fn on_append(&mut self, index: usize, len: usize) {
+            self.0.push(Event::Append(index, len));
+        }
+    }
+
+    /// Replays the events on `mirror`, reading new elements from `list` after the operation.
+    fn replay(mirror: &mut Vec<u32>, events: Vec<Event>, list: &[u32]) {
+        for event in events {
+            match event {
+                Event::Insert(index, x) => mirror.insert(index, x),
+                Event::Remove(index, x) => assert_eq!(mirror.remove(index), x),
+                Event::Splice(range, new_len) => {
+                    let start = range.start;
+                    mirror.splice(range, list[start .. start + new_len].iter().copied());
+                }
+                Event::Move(range, dest) => {
+                    let moved = mirror.drain(range).collect::<Vec<_>>();
+                    mirror.splice(dest .. dest, moved);
+                }
+                Event::Set(index, x) => mirror[index] = x,
+                Event::Reverse => mirror.reverse(),
+                Event::Reorder => mirror.copy_from_slice(list),
+                Event::Split(at) => mirror.truncate(at),
+                Event::Append(index, len) => mirror.extend_from_slice(&list[index .. index + len]),
+            }
+        }
+    }
+
+    #[test]
+    fn mirror() {
+        let mut list = TreeList::<_>::new().with_observer(Recorder::default());
+        let mut mirror = Vec::new();
+        let mut seed = 17u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0 .. 3000 {
+            let len = list.len();
+            let (a, b) = (next(len + 1), next(len + 1));
+            let (l, r) = (a.min(b), a.max(b));
+            let x = next(1000) as u32;
+            match next(17) {
+                0 => list.insert(a, x),
+                1 => list.push_front(x),
+                2 => list.push_back(x),
+                3 if len > 0 => {
+                    list.remove(a.min(len - 1));
+                }
+                4 => {
+                    list.pop_front();
+                }
+                5 => {
+                    list.pop_back();
+                }
+                6 => {
+                    list.splice(l .. r, (0 .. next(3)).map(|i| i as u32));
+                }
+                7 => {
+                    list.drain(l .. r).count();
+                }
+                8 => list.move_range(l .. r, next(len - (r - l) + 1)),
+                9 if r <= len / 2 => list.swap_ranges(l / 2 .. r / 2, len / 2 + l / 2 .. len / 2 + r / 2),
+                10 => list.reverse(),
+                11 => {
+                    let mut right = list.split_off(a);
+                    right.push_back(x);
+                    list.append(&mut right);
+                }
+                12 => list.extend_from_within(l .. r),
+                13 => list.sort(),
+                14 => list.insert_sorted(x),
+                15 if len > 0 => *list.get_mut(a.min(len - 1)).unwrap() += x,
+                _ if len > 20 => list.clear(),
+                _ => {}
+            }
+            let contents = list.iter().copied().collect::<Vec<_>>();
+            let events = core::mem::take(&mut list.observer_mut().0);
+            replay(&mut mirror, events, &contents);
+            assert_eq!(mirror, contents);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Parallel iteration with rayon, enabled by the `rayon` feature.
+//!
+//! The iterators split a list into runs of whole subtrees and single elements,
+//! dividing a subtree at its root only when a split point falls inside it.
+
+use alloc::collections::VecDeque;
+
+use rayon::iter::{
+    plumbing::{bridge, Consumer, Producer, ProducerCallback, UnindexedConsumer},
+    FromParallelIterator, IndexedParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator,
+};
+
+use super::*;
+
+/// A reference to a subtree that can be divided at its root.
+#[allow(clippy::len_without_is_empty)]
+pub trait Parts: Sized {
+    type Item;
+
+    fn len(&self) -> usize;
+
+    /// Returns the left subtree, the element at the root and the right subtree.
+    fn parts(self) -> (Option<Self>, Self::Item, Option<Self>);
+}
+
+impl<'a, T, B: Balance> Parts for &'a Node<T, B> {
+    type Item = &'a T;
+
+    fn len(&self) -> usize {
+        Node::len(self)
+    }
+
+    fn parts(self) -> (Option<Self>, &'a T, Option<Self>) {
+        (self.child(false), self.data(), self.child(true))
+    }
+}
+
+impl<'a, T, B: Balance> Parts for &'a mut Node<T, B> {
+    type Item = &'a mut T;
+
+    fn len(&self) -> usize {
+        Node::len(self)
+    }
+
+    fn parts(self) -> (Option<Self>, &'a mut T, Option<Self>) {
+        self.parts_mut()
+    }
+}
+
+enum Piece<N: Parts> {
+    Tree(N),
+    Element(N::Item),
+}
+
+impl<N: Parts> Piece<N> {
+    fn len(&self) -> usize {
+        match self {
+            Piece::Tree(node) => node.len(),
+            Piece::Element(_) => 1,
+        }
+    }
+}
+
+/// A sequence of consecutive elements, stored as subtrees and single elements.
+pub struct Pieces<N: Parts> {
+    pieces: VecDeque<Piece<N>>,
+    len: usize,
+}
+
+impl<N: Parts> Pieces<N> {
+    fn new(root: Option<N>) -> Self {
+        let len = root.as_ref().map(|node| node.len()).unwrap_or(0);
+        Self { pieces: root.map(Piece::Tree).into_iter().collect(), len }
+    }
+
+    /// Replaces a subtree at the front with its left subtree, root element and right subtree.
+    fn expand_front(&mut self, node: N) {
+        let (left, element, right) = node.parts();
+        if let Some(right) = right {
+            self.pieces.push_front(Piece::Tree(right));
+        }
+        self.pieces.push_front(Piece::Element(element));
+        if let Some(left) = left {
+            self.pieces.push_front(Piece::Tree(left));
+        }
+    }
+
+    fn expand_back(&mut self, node: N) {
+        let (left, element, right) = node.parts();
+        self.pieces.extend(left.map(Piece::Tree));
+        self.pieces.push_back(Piece::Element(element));
+        self.pieces.extend(right.map(Piece::Tree));
+    }
+}
+
+impl<N: Parts> Iterator for Pieces<N> {
+    type Item = N::Item;
+
+    fn next(&mut self) -> Option<N::Item> {
+        loop {
+            match self.pieces.pop_front()? {
+                Piece::Element(element) => {
+                    self.len -= 1;
+                    return Some(element);
+                }
+                Piece::Tree(node) => {
+                    self.expand_front(node);
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<N: Parts> DoubleEndedIterator for Pieces<N> {
+    fn next_back(&mut self) -> Option<N::Item> {
+        loop {
+            match self.pieces.pop_back()? {
+                Piece::Element(element) => {
+                    self.len -= 1;
+                    return Some(element);
+                }
+                Piece::Tree(node) => {
+                    self.expand_back(node);
+                }
+            }
+        }
+    }
+}
+
+impl<N: Parts> ExactSizeIterator for Pieces<N> {}
+
+impl<N: Parts + Send> Producer for Pieces<N> where N::Item: Send {
+    type Item = N::Item;
+    type IntoIter = Self;
+
+    fn into_iter(self) -> Self {
+        self
+    }
+
+    fn split_at(mut self, mut index: usize) -> (Self, Self) {
+        let mut left = Self { pieces: VecDeque::new(), len: index };
+        self.len -= index;
+        while index > 0 {
+            let piece = self.pieces.pop_front().unwrap();
+            let len = piece.len();
+            if len <= index {
+                index -= len;
+                left.pieces.push_back(piece);
+            } else if let Piece::Tree(node) = piece {
+                self.expand_front(node);
+            }
+        }
+        (left, self)
+    }
+}
+
+/// A parallel iterator over a subtree, created by `into_par_iter` on `&TreeList` or `&mut TreeList`.
+pub struct ParIter<N> {
+    root: Option<N>,
+}
+
+/// Parallel iterator over `&T`.
+pub type Iter<'a, T, B = Avl> = ParIter<&'a Node<T, B>>;
+/// Parallel iterator over `&mut T`.
+pub type IterMut<'a, T, B = Avl> = ParIter<&'a mut Node<T, B>>;
+
+impl<N: Parts> ParIter<N> {
+    fn len(&self) -> usize {
+        self.root.as_ref().map(|node| node.len()).unwrap_or(0)
+    }
+}
+
+impl<N: Parts + Send> ParallelIterator for ParIter<N> where N::Item: Send {
+    type Item = N::Item;
+
+    fn drive_unindexed<C: UnindexedConsumer<N::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<N: Parts + Send> IndexedParallelIterator for ParIter<N> where N::Item: Send {
+    fn len(&self) -> usize {
+        ParIter::len(self)
+    }
+
+    fn drive<C: Consumer<N::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<N::Item>>(self, callback: CB) -> CB::Output {
+        callback.callback(Pieces::new(self.root))
+    }
+}
+
+impl<'a, T: Sync, B: Balance> IntoParallelIterator for &'a TreeList<T, B> where B::Tag: Sync {
+    type Item = &'a T;
+    type Iter = Iter<'a, T, B>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        ParIter { root: self.root.as_deref() }
+    }
+}
+
+impl<'a, T: Send, B: Balance> IntoParallelIterator for &'a mut TreeList<T, B> where B::Tag: Send {
+    type Item = &'a mut T;
+    type Iter = IterMut<'a, T, B>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        ParIter { root: self.root.as_mut().map(|node| unsafe { node.as_mut().get_unchecked_mut() }) }
+    }
+}
+
+/// Builds a list per rayon job and concatenates them in order with `Node::merge`.
+impl<T: Send, B: Balance> FromParallelIterator<T> for TreeList<T, B> where B::Tag: Send {
+    fn from_par_iter<I: IntoParallelIterator<Item = T>>(iter: I) -> Self {
+        iter.into_par_iter()
+            .fold(Self::default, |mut list, element| {
+                list.push_back(element);
+                list
+            })
+            .reduce(Self::default, |mut left, mut right| {
+                left.append(&mut right);
+                left
+            })
+    }
+}
+
+impl<T: Send, B: Balance> ParallelExtend<T> for TreeList<T, B> where B::Tag: Send {
+    fn par_extend<I: IntoParallelIterator<Item = T>>(&mut self, iter: I) {
+        let mut other: Self = iter.into_par_iter().collect();
+        self.append(&mut other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::*;
+    use crate::{TreeList, SplayTreeList};
+
+    #[test]
+    fn par_iter() {
+        let mut list = (0 .. 100000u64).collect::<TreeList<_>>();
+        assert_eq!(list.par_iter().sum::<u64>(), 100000 * 99999 / 2);
+        assert_eq!(list.par_iter().copied().collect::<Vec<_>>(), (0 .. 100000).collect::<Vec<_>>());
+        assert_eq!(list.par_iter().rev().take(3).copied().collect::<Vec<_>>(), vec![99999, 99998, 99997]);
+        list.par_iter_mut().enumerate().for_each(|(i, x)| *x += i as u64);
+        assert!(list.iter().enumerate().all(|(i, &x)| x == 2 * i as u64));
+    }
+
+    #[test]
+    fn par_collect() {
+        let mut list = (0 .. 50000).into_par_iter().collect::<SplayTreeList<_>>();
+        list.par_extend((50000 .. 100000).into_par_iter().filter(|x| x % 2 == 0));
+        let expected = (0 .. 50000).chain((50000 .. 100000).filter(|x| x % 2 == 0)).collect::<Vec<_>>();
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,429 @@
+use core::{ops::*, iter::FromIterator, fmt::Debug};
+use alloc::{rc::Rc, vec, vec::Vec};
+
+use crate::tree::{self, BinaryNode, Link};
+
+pub type SharedNode<T> = Rc<PersistentNode<T>>;
+
+/// A node whose children are shared between versions.
+/// Mutation goes through `Rc::make_mut`, so only the nodes on the modified path are copied.
+#[derive(Clone)]
+pub struct PersistentNode<T> {
+    data: T,
+    len: usize,
+    height: u8,
+    children: [Option<SharedNode<T>>; 2],
+}
+
+impl<T> PersistentNode<T> {
+    pub fn new(data: T) -> Self {
+        PersistentNode {
+            data,
+            len: 1,
+            height: 1,
+            children: [None, None],
+        }
+    }
+
+    pub fn shared(data: T) -> SharedNode<T> {
+        Rc::new(Self::new(data))
+    }
+
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    pub fn child(&self, dir: bool) -> Option<&Self> {
+        self.children[dir as usize].as_deref()
+    }
+
+    pub fn leftmost<P: Fn(&Self) -> bool>(&self, predicate: P) -> Option<usize> {
+        tree::leftmost(self, predicate)
+    }
+
+    pub fn rightmost<P: Fn(&Self) -> bool>(&self, predicate: P) -> Option<usize> {
+        tree::rightmost(self, predicate)
+    }
+
+    pub fn at(&self, index: usize) -> &Self {
+        tree::at(self, index)
+    }
+
+    pub fn update(&mut self) {
+        let mut len = 1;
+        let mut height = 1;
+        for child in self.children.iter().flatten() {
+            len += child.len;
+            height = height.max(child.height + 1);
+        }
+        self.len = len;
+        self.height = height;
+    }
+
+    fn height(node: Option<&Self>) -> u8 {
+        node.map(|node| node.height).unwrap_or(0)
+    }
+}
+
+impl<T: Clone> PersistentNode<T> {
+    pub fn from_iter<I: IntoIterator<Item = T>>(data: I) -> Option<SharedNode<T>> {
+        let mut node = None;
+        for element in data {
+            node = Self::merge(node, Some(Self::shared(element)));
+        }
+        node
+    }
+
+    /// Returns the node at `index`, copying every shared node on the way down.
+    pub fn at_mut(node: &mut SharedNode<T>, mut index: usize) -> &mut Self {
+        let mut node = Rc::make_mut(node);
+        loop {
+            let left_len = node.child_len(false);
+            if index < left_len {
+                node = Rc::make_mut(node.children[0].as_mut().unwrap());
+            } else if index == left_len {
+                return node;
+            } else {
+                index -= left_len + 1;
+                node = Rc::make_mut(node.children[1].as_mut().unwrap());
+            }
+        }
+    }
+
+    /// Returns a copy of the subtree with the children of every node swapped.
+    /// # Complexity
+    /// O(len)
+    pub fn mirror(&self) -> SharedNode<T> {
+        let mut node = Self::new(self.data.clone());
+        node.children = [self.children[1].as_ref().map(|child| child.mirror()), self.children[0].as_ref().map(|child| child.mirror())];
+        node.update();
+        Rc::new(node)
+    }
+
+    pub fn rotate(self: SharedNode<T>, dir: bool) -> SharedNode<T> {
+        tree::rotate(self, dir)
+    }
+
+    pub fn balance(self: SharedNode<T>) -> SharedNode<T> {
+        tree::avl_balance(self, Self::height)
+    }
+
+    /// Joins `left`, `pivot` and `right` in this order, where `pivot` is a single node.
+    /// Only the nodes on the spine that is descended are copied, so it costs O(difference of heights).
+    pub fn join(left: Option<SharedNode<T>>, pivot: SharedNode<T>, right: Option<SharedNode<T>>) -> SharedNode<T> {
+        tree::join_spine(left, pivot, right)
+    }
+
+    pub fn merge(left: Option<SharedNode<T>>, right: Option<SharedNode<T>>) -> Option<SharedNode<T>> {
+        if let Some(left) = left {
+            if let Some(right) = right {
+                Some(left.append(right))
+            } else {
+                Some(left)
+            }
+        } else {
+            right
+        }
+    }
+
+    pub fn append(self: SharedNode<T>, other: SharedNode<T>) -> SharedNode<T> {
+        tree::append(self, other)
+    }
+
+    pub fn split_at(self: SharedNode<T>, at: usize) -> (Option<SharedNode<T>>, Option<SharedNode<T>>) {
+        tree::split_at(self, at)
+    }
+}
+
+impl<T> BinaryNode for PersistentNode<T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn child(&self, dir: bool) -> Option<&Self> {
+        PersistentNode::child(self, dir)
+    }
+}
+
+impl<T: Clone> Link for SharedNode<T> {
+    type Node = PersistentNode<T>;
+
+    fn node(&self) -> &PersistentNode<T> {
+        self
+    }
+
+    /// Copies the node first if it is shared.
+    fn replace_child(&mut self, dir: bool, replacement: Option<Self>) -> Option<Self> {
+        core::mem::replace(&mut Rc::make_mut(self).children[dir as usize], replacement)
+    }
+
+    fn update(&mut self) {
+        Rc::make_mut(self).update();
+    }
+
+    fn heavy_side(left: Option<&PersistentNode<T>>, right: Option<&PersistentNode<T>>) -> Option<bool> {
+        tree::avl_heavy_side(left, right, PersistentNode::height)
+    }
+
+    fn balance(self) -> Self {
+        PersistentNode::balance(self)
+    }
+
+    fn join(left: Option<Self>, pivot: Self, right: Option<Self>) -> Self {
+        PersistentNode::join(left, pivot, right)
+    }
+}
+
+/// An immutable list whose operations return new versions in O(log(len)).
+/// Versions share structure, so keeping old versions around and cloning are cheap.
+pub struct PersistentTreeList<T> {
+    root: Option<SharedNode<T>>,
+}
+
+impl<T> PersistentTreeList<T> {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+        }
+    }
+
+    pub fn raw(root: Option<SharedNode<T>>) -> Self {
+        Self {
+            root,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.as_ref().map(|node| node.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// # Complexity
+    /// O(log(len))
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len() {
+            Some(self.root.as_ref().unwrap().at(index).data())
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(self.root.as_deref())
+    }
+
+    pub fn leftmost<P: Fn(&T) -> bool>(&self, predicate: P) -> Option<usize> {
+        self.root.as_ref().and_then(|node| node.leftmost(|n| predicate(n.data())))
+    }
+
+    pub fn rightmost<P: Fn(&T) -> bool>(&self, predicate: P) -> Option<usize> {
+        self.root.as_ref().and_then(|node| node.rightmost(|n| predicate(n.data())))
+    }
+}
+
+impl<T: Clone> PersistentTreeList<T> {
+    /// Returns a new version with `element` inserted at the given index.
+    /// # Panics
+    /// Panics if index is out of bounds (i.e. greater than the length of the list).
+    /// # Complexity
+    /// O(log(len))
+    pub fn insert(&self, index: usize, element: T) -> Self {
+        assert!(index <= self.len());
+        let (left, right) = self.split_off(index);
+        Self::raw(PersistentNode::merge(PersistentNode::merge(left.root, Some(PersistentNode::shared(element))), right.root))
+    }
+
+    pub fn push_front(&self, element: T) -> Self {
+        Self::raw(PersistentNode::merge(Some(PersistentNode::shared(element)), self.root.clone()))
+    }
+
+    pub fn push_back(&self, element: T) -> Self {
+        Self::raw(PersistentNode::merge(self.root.clone(), Some(PersistentNode::shared(element))))
+    }
+
+    /// Returns a new version without the element at the given index.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn remove(&self, index: usize) -> Self {
+        assert!(index < self.len());
+        let (left, right) = self.split_off(index);
+        let (_, right) = right.split_off(1);
+        left.append(&right)
+    }
+
+    /// Returns a new version with the element at the given index replaced by `element`.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn set(&self, index: usize, element: T) -> Self {
+        assert!(index < self.len());
+        let mut root = self.root.clone().unwrap();
+        PersistentNode::at_mut(&mut root, index).data = element;
+        Self::raw(Some(root))
+    }
+
+    /// Returns a new version that is the concatenation of `self` and `other`.
+    /// # Complexity
+    /// O(log(len + other.len()))
+    pub fn append(&self, other: &Self) -> Self {
+        Self::raw(PersistentNode::merge(self.root.clone(), other.root.clone()))
+    }
+
+    /// Returns a new version with the elements in reverse order.
+    /// Nothing can be shared with `self`, so every node is copied.
+    /// # Complexity
+    /// O(len)
+    pub fn reverse(&self) -> Self {
+        Self::raw(self.root.as_ref().map(|root| root.mirror()))
+    }
+
+    /// Splits the list into two versions at the given index.
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn split_off(&self, at: usize) -> (Self, Self) {
+        assert!(at <= self.len());
+        if let Some(root) = self.root.clone() {
+            let (left, right) = root.split_at(at);
+            (Self::raw(left), Self::raw(right))
+        } else {
+            (Self::new(), Self::new())
+        }
+    }
+}
+
+impl<T> Clone for PersistentTreeList<T> {
+    /// O(1)
+    fn clone(&self) -> Self {
+        Self::raw(self.root.clone())
+    }
+}
+
+impl<T> Default for PersistentTreeList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> FromIterator<T> for PersistentTreeList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::raw(PersistentNode::from_iter(iter))
+    }
+}
+
+impl<T> Index<usize> for PersistentTreeList<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        assert!(index < self.len());
+        self.root.as_ref().unwrap().at(index).data()
+    }
+}
+
+impl<T: Debug> Debug for PersistentTreeList<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("PersistentTreeList ")?;
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, T> {
+    stack: Vec<&'a PersistentNode<T>>,
+}
+impl<'a, T> Iter<'a, T> {
+    pub fn new(root: Option<&'a PersistentNode<T>>) -> Self {
+        let mut this = Self { stack: vec![] };
+        this.add(root);
+        this
+    }
+
+    fn add(&mut self, mut node: Option<&'a PersistentNode<T>>) {
+        while let Some(child) = node {
+            self.stack.push(child);
+            node = child.child(false);
+        }
+    }
+}
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        let element = node.data();
+        self.add(node.child(true));
+        Some(element)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PersistentNode, PersistentTreeList};
+
+    fn depth<T>(node: Option<&PersistentNode<T>>) -> usize {
+        node.map(|node| 1 + depth(node.child(false)).max(depth(node.child(true)))).unwrap_or(0)
+    }
+
+    #[test]
+    fn height() {
+        let n = 100000;
+        let bound = 1.44 * (n as f64).log2() + 2.0;
+        let (mut front, mut back, mut middle) = (PersistentTreeList::new(), PersistentTreeList::new(), PersistentTreeList::new());
+        for i in 0 .. n {
+            front = front.push_front(i);
+            back = back.push_back(i);
+            middle = middle.insert(middle.len() / 2, i);
+        }
+        for list in [&front, &back, &middle] {
+            assert!(depth(list.root.as_deref()) as f64 <= bound);
+        }
+        assert_eq!(front[0], n - 1);
+        let (left, right) = middle.split_off(12345);
+        let joined = right.append(&front).append(&left);
+        assert_eq!(joined.len(), 2 * n);
+        assert!(depth(joined.root.as_deref()) as f64 <= 1.44 * (2.0 * n as f64).log2() + 2.0);
+    }
+
+    #[test]
+    fn versions() {
+        let v0 = (0 .. 10).collect::<PersistentTreeList<_>>();
+        let v1 = v0.insert(3, 100);
+        let v2 = v1.remove(0);
+        let v3 = v2.set(5, 200);
+        assert_eq!(v0.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(v1.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2, 100, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(v2.iter().copied().collect::<Vec<_>>(), vec![1, 2, 100, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(v3.iter().copied().collect::<Vec<_>>(), vec![1, 2, 100, 3, 4, 200, 6, 7, 8, 9]);
+        assert_eq!(v3[5], 200);
+        assert_eq!(v3.get(5), Some(&200));
+        assert_eq!(v3.get(10), None);
+        let reversed = v3.reverse();
+        assert_eq!(reversed.iter().copied().collect::<Vec<_>>(), vec![9, 8, 7, 6, 200, 4, 3, 100, 2, 1]);
+        assert_eq!(reversed.reverse().iter().copied().collect::<Vec<_>>(), v3.iter().copied().collect::<Vec<_>>());
+        let descending = v0.reverse();
+        assert_eq!(descending.leftmost(|&x| x < 5), Some(5));
+        assert_eq!(descending.rightmost(|&x| x > 5), Some(3));
+    }
+
+    #[test]
+    fn split_append() {
+        let list = (0 .. 20).collect::<PersistentTreeList<_>>();
+        let (left, right) = list.split_off(7);
+        let joined = right.append(&left);
+        assert_eq!(left.len(), 7);
+        assert_eq!(right.len(), 13);
+        assert_eq!(joined.iter().copied().collect::<Vec<_>>(), (7 .. 20).chain(0 .. 7).collect::<Vec<_>>());
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), (0 .. 20).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,345 @@
+//! Replicated Growable Array: a sequence CRDT.
+//!
+//! Every element gets an `Id` that never changes, and removed elements stay as tombstones,
+//! so operations can be applied by every replica in any causal order and the replicas converge.
+//! An `Id` has to lead to the position of its element, which `TreeList` cannot do without parent links,
+//! so like `EulerTourForest` the sequence is a splay tree of `arena`: all operations take amortized O(log n).
+
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+use crate::arena::{SplayArena, Summarize, NIL};
+
+/// Identifier of an element, unique among all replicas.
+/// Ordered by the Lamport clock `counter` first, and then by `replica`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Id {
+    pub counter: u64,
+    pub replica: u64,
+}
+
+/// An edit to be sent to the other replicas.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Op<T> {
+    /// Inserts `value` right after the element `after`, or at the front if `None`.
+    Insert { id: Id, after: Option<Id>, value: T },
+    Remove { id: Id },
+}
+
+struct Element<T> {
+    id: Id,
+    /// `None` for a tombstone.
+    value: Option<T>,
+}
+
+impl<T> Summarize for Element<T> {
+    /// Number of visible elements.
+    type Summary = usize;
+
+    fn summary(&self) -> usize {
+        self.value.is_some() as usize
+    }
+
+    fn combine(left: &usize, right: &usize) -> usize {
+        left + right
+    }
+}
+
+/// A list replicated among several replicas, each identified by a distinct number.
+/// Local edits return an `Op` to be delivered to the other replicas through `apply_remote`.
+///
+/// Reads by position or id (`get`, `id_at`, `index_of`) take `&mut self`, because they splay the element they find
+/// to the root. `len` and `iter` only take `&self`.
+pub struct Rga<T> {
+    replica: u64,
+    /// Largest counter seen so far.
+    clock: u64,
+    elements: SplayArena<Element<T>>,
+    /// Root of the sequence, including tombstones.
+    root: usize,
+    ids: BTreeMap<Id, usize>,
+    /// Remote operations waiting for the element they refer to, by the id of that element.
+    pending: BTreeMap<Id, Vec<Op<T>>>,
+    /// Number of operations in `pending`.
+    pending_len: usize,
+}
+
+impl<T: Clone> Rga<T> {
+    pub fn new(replica: u64) -> Self {
+        Self { replica, clock: 0, elements: SplayArena::new(), root: NIL, ids: BTreeMap::new(), pending: BTreeMap::new(), pending_len: 0 }
+    }
+
+    pub fn replica(&self) -> u64 {
+        self.replica
+    }
+
+    /// Returns the number of visible elements.
+    pub fn len(&self) -> usize {
+        self.elements.sum(self.root).copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of remote operations waiting for the operations they depend on.
+    pub fn pending_len(&self) -> usize {
+        self.pending_len
+    }
+
+    /// # Complexity
+    /// O(log(n)) amortized where n counts tombstones
+    pub fn get(&mut self, index: usize) -> Option<&T> {
+        if index >= self.len() {
+            return None;
+        }
+        let x = self.find_visible(index);
+        self.elements.get(x).value.as_ref()
+    }
+
+    /// Returns the id of the `index`-th visible element.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(n)) amortized
+    pub fn id_at(&mut self, index: usize) -> Id {
+        assert!(index < self.len());
+        let x = self.find_visible(index);
+        self.elements.get(x).id
+    }
+
+    /// Returns the visible index of the element `id`, or `None` if it is unknown or removed.
+    /// # Complexity
+    /// O(log(n)) amortized
+    pub fn index_of(&mut self, id: Id) -> Option<usize> {
+        let x = *self.ids.get(&id)?;
+        self.elements.get(x).value.as_ref()?;
+        self.root = x;
+        Some(self.elements.sum_before(x).copied().unwrap_or(0))
+    }
+
+    /// Inserts `value` before the `index`-th visible element and returns the operation for the other replicas.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(n)) amortized
+    pub fn insert(&mut self, index: usize, value: T) -> Op<T> {
+        assert!(index <= self.len());
+        let after = if index == 0 { None } else { Some(self.id_at(index - 1)) };
+        self.clock += 1;
+        let id = Id { counter: self.clock, replica: self.replica };
+        let op = Op::Insert { id, after, value };
+        // A local operation only refers to known elements, so it is never deferred.
+        let _ = self.integrate(op.clone());
+        op
+    }
+
+    /// Removes the `index`-th visible element and returns the operation for the other replicas.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(n)) amortized
+    pub fn remove(&mut self, index: usize) -> Op<T> {
+        let id = self.id_at(index);
+        let op = Op::Remove { id };
+        // A local operation only refers to known elements, so it is never deferred.
+        let _ = self.integrate(op.clone());
+        op
+    }
+
+    /// Applies an operation from another replica. Applying an operation twice has no effect.
+    /// An operation whose element is not known yet is kept until the operation it depends on arrives.
+    /// # Complexity
+    /// O(log(n) + log(p)) amortized for p pending operations, plus O(k) for the k elements inserted concurrently
+    /// at the same place, and the same for each pending operation that it releases
+    pub fn apply_remote(&mut self, op: Op<T>) {
+        let mut ready = vec![op];
+        while let Some(op) = ready.pop() {
+            let inserted = match op {
+                Op::Insert { id, .. } => Some(id),
+                Op::Remove { .. } => None,
+            };
+            match self.integrate(op) {
+                Ok(()) => {
+                    // Only an insertion can be waited for, and only the operations waiting for it become ready.
+                    if let Some(released) = inserted.and_then(|id| self.pending.remove(&id)) {
+                        self.pending_len -= released.len();
+                        ready.extend(released);
+                    }
+                }
+                Err((awaited, op)) => {
+                    self.pending.entry(awaited).or_default().push(op);
+                    self.pending_len += 1;
+                }
+            }
+        }
+    }
+
+    /// Returns an iterator over the visible elements.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.elements.iter(self.root).filter_map(|element| element.value.as_ref())
+    }
+
+    /// Returns the operation back with the id of the unknown element it refers to.
+    fn integrate(&mut self, op: Op<T>) -> Result<(), (Id, Op<T>)> {
+        match op {
+            Op::Insert { id, after, value } => {
+                if self.ids.contains_key(&id) {
+                    return Ok(());
+                }
+                let mut position = match after {
+                    None => 0,
+                    Some(after) => match self.ids.get(&after) {
+                        Some(&x) => self.position(x) + 1,
+                        None => return Err((after, Op::Insert { id, after: Some(after), value })),
+                    },
+                };
+                // Elements inserted after `after` with larger ids, and everything inserted after them, come first.
+                while position < self.elements.len(self.root) {
+                    let y = self.at(position);
+                    if self.elements.get(y).id < id {
+                        break;
+                    }
+                    position += 1;
+                }
+                self.clock = self.clock.max(id.counter);
+                let x = self.elements.alloc(Element { id, value: Some(value) });
+                self.ids.insert(id, x);
+                let (left, right) = self.elements.split(self.root, position);
+                let root = self.elements.merge(left, x);
+                self.root = self.elements.merge(root, right);
+                Ok(())
+            }
+            Op::Remove { id } => {
+                let x = match self.ids.get(&id) {
+                    Some(&x) => x,
+                    None => return Err((id, Op::Remove { id })),
+                };
+                self.elements.modify(x, |element| element.value = None);
+                self.root = x;
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the position of `x` counting tombstones.
+    fn position(&mut self, x: usize) -> usize {
+        self.root = x;
+        self.elements.position(x)
+    }
+
+    /// Returns the node at `position` counting tombstones, moved to the root.
+    fn at(&mut self, position: usize) -> usize {
+        self.root = self.elements.at(self.root, position);
+        self.root
+    }
+
+    /// Returns the `index`-th visible node, moved to the root.
+    fn find_visible(&mut self, index: usize) -> usize {
+        self.root = self.elements.find(self.root, index, |element| element.value.is_some() as usize, |&visible| visible);
+        self.root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(rga: &Rga<u32>) -> Vec<u32> {
+        rga.iter().copied().collect()
+    }
+
+    #[test]
+    fn concurrent_edits_converge() {
+        let n = 3;
+        let mut replicas = (0 .. n as u64).map(Rga::new).collect::<Vec<_>>();
+        // outbox[i] holds the operations of replica i; delivered[j][i] counts those applied by replica j.
+        let mut outbox = vec![vec![]; n];
+        let mut delivered = vec![vec![0; n]; n];
+        let mut seed = 13u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for step in 0 .. 3000 {
+            let i = next(n);
+            let replica = &mut replicas[i];
+            let len = replica.len();
+            match next(4) {
+                0 | 1 => {
+                    let op = replica.insert(next(len + 1), step);
+                    outbox[i].push(op);
+                }
+                2 if len > 0 => {
+                    let op = replica.remove(next(len));
+                    outbox[i].push(op);
+                }
+                _ => {
+                    // Deliver some operations of another replica, which may arrive before their dependencies.
+                    let j = next(n);
+                    if i != j {
+                        let end = (delivered[i][j] + next(4)).min(outbox[j].len());
+                        for op in &outbox[j][delivered[i][j] .. end] {
+                            replica.apply_remote(op.clone());
+                        }
+                        delivered[i][j] = end;
+                    }
+                }
+            }
+            let replica = &mut replicas[i];
+            for index in 0 .. replica.len() {
+                let id = replica.id_at(index);
+                assert_eq!(replica.index_of(id), Some(index));
+            }
+        }
+        for (i, replica) in replicas.iter_mut().enumerate() {
+            for (j, ops) in outbox.iter().enumerate() {
+                // Delivering everything again checks that duplicates are ignored.
+                if i != j {
+                    for op in ops {
+                        replica.apply_remote(op.clone());
+                    }
+                }
+            }
+            assert_eq!(replica.pending_len(), 0);
+        }
+        let expected = contents(&replicas[0]);
+        assert!(!expected.is_empty());
+        for replica in &replicas {
+            assert_eq!(contents(replica), expected);
+        }
+    }
+
+    #[test]
+    fn concurrent_inserts_at_same_place() {
+        let mut a = Rga::new(1);
+        let mut b = Rga::new(2);
+        let base = a.insert(0, 'x');
+        b.apply_remote(base);
+        let from_a = a.insert(1, 'a');
+        let from_b = b.insert(1, 'b');
+        a.apply_remote(from_b);
+        b.apply_remote(from_a);
+        assert_eq!(a.iter().collect::<Vec<_>>(), b.iter().collect::<Vec<_>>());
+        // Equal counters are ordered by replica, and larger ids come first.
+        assert_eq!(a.iter().copied().collect::<Vec<_>>(), vec!['x', 'b', 'a']);
+        let removal = a.remove(0);
+        let id = b.id_at(0);
+        b.apply_remote(removal);
+        assert_eq!(b.index_of(id), None);
+        assert_eq!(b.get(0), Some(&'b'));
+    }
+
+    #[test]
+    fn operations_delivered_in_reverse() {
+        let mut a = Rga::new(1);
+        let mut b = Rga::new(2);
+        let mut ops = (0 .. 1000).map(|i| a.insert(i, i as u32)).collect::<Vec<_>>();
+        ops.push(a.remove(0));
+        for (waiting, op) in ops.into_iter().rev().enumerate() {
+            assert_eq!(b.pending_len(), waiting);
+            b.apply_remote(op);
+        }
+        assert_eq!(b.pending_len(), 0);
+        assert_eq!(contents(&b), (1 .. 1000).collect::<Vec<_>>());
+    }
+}
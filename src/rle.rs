@@ -0,0 +1,449 @@
+//! Run-length encoded list: each node of a `Node` tree holds a value and how many times it repeats.
+//! Adjacent runs always hold different values, so the tree has one node per run.
+//!
+//! The length of a node counts runs, and the `RunLength` aggregate counts elements,
+//! so the run containing a position is found in O(log(runs)).
+
+use core::{ops::*, fmt::Debug, iter::FromIterator};
+
+use super::bounds;
+use crate::{balance::Avl, iter, monoid::{Aggregate, Monoid}, node::{self, Node, PinnedNode}};
+
+/// A value and how many times it repeats.
+pub(crate) struct Run<T> {
+    value: T,
+    count: usize,
+}
+
+/// Counts the elements in the runs of every subtree.
+pub(crate) struct RunLength;
+
+impl Monoid for RunLength {
+    type Value = usize;
+
+    fn identity() -> usize {
+        0
+    }
+
+    fn op(a: &usize, b: &usize) -> usize {
+        a + b
+    }
+}
+
+impl<T> Aggregate<Run<T>> for RunLength {
+    fn lift(run: &Run<T>) -> usize {
+        run.count
+    }
+}
+
+pub(crate) type RunNode<T> = Node<Run<T>, Avl, RunLength>;
+pub(crate) type Runs<T> = node::Tree<Run<T>, Avl, RunLength>;
+
+/// Creates a single run of `count` copies of `value`.
+pub(crate) fn run<T>(value: T, count: usize) -> PinnedNode<Run<T>, Avl, RunLength> {
+    Node::pin(Run { value, count }, &mut ())
+}
+
+fn elements<T>(node: Option<&RunNode<T>>) -> usize {
+    node.map(|node| *node.sum()).unwrap_or(0)
+}
+
+pub(crate) fn len<T>(tree: &Runs<T>) -> usize {
+    elements(tree.as_deref())
+}
+
+pub(crate) fn run_count<T>(tree: &Runs<T>) -> usize {
+    tree.as_ref().map(|node| node.len()).unwrap_or(0)
+}
+
+/// Returns the value of the run containing the `index`-th element.
+pub(crate) fn get<T>(tree: &Runs<T>, mut index: usize) -> Option<&T> {
+    if index >= len(tree) {
+        return None;
+    }
+    let mut node = tree.as_deref().unwrap();
+    loop {
+        let left_len = elements(node.child(false));
+        let count = node.data().count;
+        if index < left_len {
+            node = node.child(false).unwrap();
+        } else if index < left_len + count {
+            return Some(&node.data().value);
+        } else {
+            index -= left_len + count;
+            node = node.child(true).unwrap();
+        }
+    }
+}
+
+/// Changes the length of a single run.
+fn set_count<T>(mut node: PinnedNode<Run<T>, Avl, RunLength>, count: usize) -> PinnedNode<Run<T>, Avl, RunLength> {
+    let node_mut = unsafe { node.as_mut().get_unchecked_mut() };
+    node_mut.data_mut().count = count;
+    node_mut.update();
+    node
+}
+
+/// Splits before the `at`-th element, cutting a run in two if needed.
+pub(crate) fn split_at<T: Clone>(tree: Runs<T>, at: usize) -> (Runs<T>, Runs<T>) {
+    let (left, node, right) = match tree {
+        Some(node) => node.detach(),
+        None => return (None, None),
+    };
+    let left_len = elements(left.as_deref());
+    let count = node.data().count;
+    if at <= left_len {
+        let (a, b) = split_at(left, at);
+        (a, Some(Node::join(b, node, right)))
+    } else if at >= left_len + count {
+        let (a, b) = split_at(right, at - left_len - count);
+        (Some(Node::join(left, node, a)), b)
+    } else {
+        let offset = at - left_len;
+        let tail = run(node.data().value.clone(), count - offset);
+        let node = set_count(node, offset);
+        (Some(Node::join(left, node, None)), Some(Node::join(None, tail, right)))
+    }
+}
+
+/// Concatenates without merging the runs at the boundary.
+pub(crate) fn concat<T>(left: Runs<T>, right: Runs<T>) -> Runs<T> {
+    Node::merge(left, right)
+}
+
+/// Concatenates, merging the runs at the boundary if they hold equal values.
+fn merge<T: Eq>(left: Runs<T>, right: Runs<T>) -> Runs<T> {
+    let (left_node, right_node) = match (&left, &right) {
+        (Some(l), Some(r)) => (l, r),
+        _ => return left.or(right),
+    };
+    if left_node.at(left_node.len() - 1).data().value == right_node.at(0).data().value {
+        let k = left_node.len() - 1;
+        let (left, last) = left.unwrap().split_at(k);
+        let (first, right) = right.unwrap().split_at(1);
+        let last = last.unwrap();
+        let count = last.data().count + first.unwrap().data().count;
+        Some(Node::join(left, set_count(last, count), right))
+    } else {
+        concat(left, right)
+    }
+}
+
+/// Calls `f` with the position, the length and the value of each run in order.
+pub(crate) fn for_each_mut<T, F: FnMut(usize, usize, &mut T)>(tree: &mut Runs<T>, offset: usize, f: &mut F) {
+    fn walk<T, F: FnMut(usize, usize, &mut T)>(node: &mut RunNode<T>, offset: usize, f: &mut F) {
+        let left_len = elements(node.child(false));
+        let (left, run, right) = node.parts_mut();
+        if let Some(left) = left {
+            walk(left, offset, f);
+        }
+        f(offset + left_len, run.count, &mut run.value);
+        if let Some(right) = right {
+            walk(right, offset + left_len + run.count, f);
+        }
+    }
+    if let Some(root) = tree {
+        walk(unsafe { root.as_mut().get_unchecked_mut() }, offset, f);
+    }
+}
+
+/// A list of `T` stored as runs of equal values.
+/// Positional operations take O(log(runs)) regardless of the number of elements.
+pub struct RleTreeList<T> {
+    root: Runs<T>,
+}
+
+impl<T: Eq + Clone> RleTreeList<T> {
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        len(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns the number of runs.
+    pub fn run_count(&self) -> usize {
+        run_count(&self.root)
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        get(&self.root, index)
+    }
+
+    /// Inserts `count` copies of `value` before the `index`-th element.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(runs))
+    pub fn insert_run(&mut self, index: usize, value: T, count: usize) {
+        assert!(index <= self.len());
+        if count == 0 {
+            return;
+        }
+        let (left, right) = split_at(self.root.take(), index);
+        self.root = merge(merge(left, Some(run(value, count))), right);
+    }
+
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.insert_run(index, value, 1);
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        self.insert_run(0, value, 1);
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        self.push_run(value, 1);
+    }
+
+    /// Appends `count` copies of `value`.
+    pub fn push_run(&mut self, value: T, count: usize) {
+        if count > 0 {
+            self.root = merge(self.root.take(), Some(run(value, count)));
+        }
+    }
+
+    /// Removes and returns the element at the specified position in the list.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(runs))
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len());
+        let (left, right) = split_at(self.root.take(), index);
+        let (removed, right) = split_at(right, 1);
+        self.root = merge(left, right);
+        removed.unwrap().recycle().0.value
+    }
+
+    /// Replaces every element in `range` with `value`.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(runs))
+    pub fn assign<R: RangeBounds<usize>>(&mut self, range: R, value: T) {
+        let (l, r) = bounds(range, self.len());
+        let (left, rest) = split_at(self.root.take(), l);
+        let (_, right) = split_at(rest, r - l);
+        let middle = if l < r { Some(run(value, r - l)) } else { None };
+        self.root = merge(merge(left, middle), right);
+    }
+
+    /// Moves all the elements of `other` into `self`, leaving `other` empty.
+    /// # Complexity
+    /// O(log(runs + other.runs))
+    pub fn append(&mut self, other: &mut Self) {
+        self.root = merge(self.root.take(), other.root.take());
+    }
+
+    /// Splits the list into two at the given index. Returns the second list.
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    /// # Complexity
+    /// O(log(runs))
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len());
+        let (left, right) = split_at(self.root.take(), at);
+        self.root = left;
+        Self { root: right }
+    }
+
+    /// Returns an iterator over `(value, count)` of each run.
+    pub fn runs(&self) -> RunIter<'_, T> {
+        RunIter::new(self.root.as_deref())
+    }
+
+    /// Returns an iterator over the elements, repeating each value of a run.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { runs: self.runs(), current: None }
+    }
+}
+
+impl<T: Eq + Clone> Default for RleTreeList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Eq + Clone> FromIterator<T> for RleTreeList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::new();
+        let mut run: Option<(T, usize)> = None;
+        for value in iter {
+            match &mut run {
+                Some((current, count)) if *current == value => *count += 1,
+                _ => {
+                    if let Some((value, count)) = run.take() {
+                        list.push_run(value, count);
+                    }
+                    run = Some((value, 1));
+                }
+            }
+        }
+        if let Some((value, count)) = run {
+            list.push_run(value, count);
+        }
+        list
+    }
+}
+
+impl<T: Eq + Clone> Index<usize> for RleTreeList<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        assert!(index < self.len());
+        self.get(index).unwrap()
+    }
+}
+
+impl<T: Eq + Clone + Debug> Debug for RleTreeList<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("RleTreeList ")?;
+        f.debug_list().entries(self.runs()).finish()
+    }
+}
+
+pub struct RunIter<'a, T> {
+    runs: iter::Iter<'a, Run<T>, Avl, RunLength>,
+}
+impl<'a, T> RunIter<'a, T> {
+    fn new(root: Option<&'a RunNode<T>>) -> Self {
+        Self { runs: iter::Iter::new(root) }
+    }
+
+    /// Creates an iterator starting at the run that contains the `index`-th element,
+    /// and returns the position where that run starts.
+    pub(crate) fn starting_at(root: Option<&'a RunNode<T>>, mut index: usize) -> (Self, usize) {
+        let mut node = root;
+        let (mut runs, mut start) = (0, 0);
+        while let Some(current) = node {
+            let left_len = elements(current.child(false));
+            let count = current.data().count;
+            if index < left_len {
+                node = current.child(false);
+            } else if index < left_len + count {
+                runs += current.child_len(false);
+                start += left_len;
+                break;
+            } else {
+                index -= left_len + count;
+                runs += current.child_len(false) + 1;
+                start += left_len + count;
+                node = current.child(true);
+            }
+        }
+        (Self { runs: iter::Iter::starting_at(root, runs) }, start)
+    }
+}
+impl<'a, T> Iterator for RunIter<'a, T> {
+    type Item = (&'a T, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let run = self.runs.next()?;
+        Some((&run.value, run.count))
+    }
+}
+
+pub struct Iter<'a, T> {
+    runs: RunIter<'a, T>,
+    current: Option<(&'a T, usize)>,
+}
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            if let Some((value, remaining)) = &mut self.current {
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    return Some(value);
+                }
+            }
+            self.current = Some(self.runs.next()?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(list: &RleTreeList<u8>, expected: &[u8]) {
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+        let runs = list.runs().collect::<Vec<_>>();
+        assert!(runs.windows(2).all(|w| w[0].0 != w[1].0), "adjacent runs are not merged: {:?}", list);
+        assert_eq!(list.run_count(), runs.len());
+        fn balanced(node: Option<&RunNode<u8>>) -> bool {
+            node.map(|node| {
+                let h = |dir| node.child(dir).map(|c| *c.tag()).unwrap_or(0);
+                h(false).max(h(true)) <= h(false).min(h(true)) + 1 && balanced(node.child(false)) && balanced(node.child(true))
+            }).unwrap_or(true)
+        }
+        assert!(balanced(list.root.as_deref()));
+    }
+
+    #[test]
+    fn random_operations() {
+        let mut list = RleTreeList::new();
+        let mut expected = vec![];
+        let mut seed = 1u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0 .. 3000 {
+            let value = next(3) as u8;
+            match next(5) {
+                0 => {
+                    let at = next(expected.len() + 1);
+                    list.insert(at, value);
+                    expected.insert(at, value);
+                }
+                1 if !expected.is_empty() => {
+                    let at = next(expected.len());
+                    assert_eq!(list.remove(at), expected.remove(at));
+                }
+                2 => {
+                    let (a, b) = (next(expected.len() + 1), next(expected.len() + 1));
+                    list.assign(a.min(b) .. a.max(b), value);
+                    expected[a.min(b) .. a.max(b)].iter_mut().for_each(|x| *x = value);
+                }
+                _ => {
+                    let at = next(expected.len() + 1);
+                    let mut right = list.split_off(at);
+                    let mut expected_right = expected.split_off(at);
+                    check(&right, &expected_right);
+                    list.append(&mut right);
+                    expected.append(&mut expected_right);
+                }
+            }
+            check(&list, &expected);
+        }
+        for (i, x) in expected.iter().enumerate() {
+            assert_eq!(&list[i], x);
+        }
+    }
+
+    #[test]
+    fn runs() {
+        let mut list = "aaabbbbcaa".bytes().collect::<RleTreeList<_>>();
+        assert_eq!(list.runs().map(|(&v, c)| (v, c)).collect::<Vec<_>>(), vec![(b'a', 3), (b'b', 4), (b'c', 1), (b'a', 2)]);
+        list.assign(3 .. 8, b'a');
+        assert_eq!(list.run_count(), 1);
+        assert_eq!(list.len(), 10);
+        list.insert_run(5, b'z', 1_000_000_000);
+        assert_eq!(list.run_count(), 3);
+        assert_eq!(list[1_000_000_004], b'z');
+        assert_eq!(list[1_000_000_005], b'a');
+    }
+}
@@ -0,0 +1,480 @@
+use core::{fmt, ops::*};
+use alloc::{borrow::ToOwned, string::{String, ToString}, vec::Vec};
+
+use crate::{balance::Avl, bounds, iter::Iter, monoid::{Aggregate, Monoid}, node::{self, Node, PinnedNode}};
+
+/// Target maximum number of bytes stored in a single leaf chunk.
+const CHUNK: usize = 128;
+
+/// Sizes of a piece of text in every unit a `Rope` can be indexed by.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct TextSummary {
+    pub bytes: usize,
+    pub chars: usize,
+    pub utf16: usize,
+    /// Number of `'\n'`.
+    pub lines: usize,
+}
+
+impl TextSummary {
+    pub fn of(text: &str) -> Self {
+        Self {
+            bytes: text.len(),
+            chars: text.chars().count(),
+            utf16: text.encode_utf16().count(),
+            lines: text.bytes().filter(|&b| b == b'\n').count(),
+        }
+    }
+
+    pub fn of_char(c: char) -> Self {
+        Self {
+            bytes: c.len_utf8(),
+            chars: 1,
+            utf16: c.len_utf16(),
+            lines: (c == '\n') as usize,
+        }
+    }
+}
+
+impl Add for TextSummary {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            bytes: self.bytes + other.bytes,
+            chars: self.chars + other.chars,
+            utf16: self.utf16 + other.utf16,
+            lines: self.lines + other.lines,
+        }
+    }
+}
+
+/// A piece of the text with its summary, so that updating a node does not rescan the text.
+struct Chunk {
+    text: String,
+    summary: TextSummary,
+}
+
+impl Chunk {
+    fn new(text: String) -> Self {
+        let summary = TextSummary::of(&text);
+        Self { text, summary }
+    }
+}
+
+/// Sums the summaries of the chunks in every subtree.
+struct TextMetrics;
+
+impl Monoid for TextMetrics {
+    type Value = TextSummary;
+
+    fn identity() -> TextSummary {
+        TextSummary::default()
+    }
+
+    fn op(a: &TextSummary, b: &TextSummary) -> TextSummary {
+        *a + *b
+    }
+}
+
+impl Aggregate<Chunk> for TextMetrics {
+    fn lift(chunk: &Chunk) -> TextSummary {
+        chunk.summary
+    }
+}
+
+type RopeNode = Node<Chunk, Avl, TextMetrics>;
+type Tree = node::Tree<Chunk, Avl, TextMetrics>;
+
+fn leaf(text: String) -> PinnedNode<Chunk, Avl, TextMetrics> {
+    Node::pin(Chunk::new(text), &mut ())
+}
+
+/// Builds a tree from `text`, cutting it into chunks of at most `CHUNK` bytes of similar size.
+/// A chunk may exceed `CHUNK` by a few bytes to end on a character boundary.
+fn build(text: &str) -> Tree {
+    let pieces = text.len().div_ceil(CHUNK);
+    let mut nodes = Vec::with_capacity(pieces);
+    let mut rest = text;
+    for i in 0 .. pieces {
+        let mut at = rest.len() / (pieces - i);
+        while !rest.is_char_boundary(at) {
+            at += 1;
+        }
+        let (chunk, tail) = rest.split_at(at);
+        if !chunk.is_empty() {
+            nodes.push(leaf(chunk.to_owned()));
+        }
+        rest = tail;
+    }
+    Node::build(nodes)
+}
+
+fn chars(node: Option<&RopeNode>) -> usize {
+    node.map(|node| node.sum().chars).unwrap_or(0)
+}
+
+/// Splits the subtree before the `at`-th character, cutting a chunk if needed.
+fn split_at_char(node: PinnedNode<Chunk, Avl, TextMetrics>, at: usize) -> (Tree, Tree) {
+    let total = node.sum().chars;
+    assert!(at <= total);
+    if at == 0 {
+        return (None, Some(node));
+    } else if at == total {
+        return (Some(node), None);
+    }
+    let left_chars = chars(node.child(false));
+    let own_chars = node.data().summary.chars;
+    let (left, pivot, right) = node.detach();
+    if at <= left_chars {
+        let (left, middle) = split_at_char(left.unwrap(), at);
+        (left, Some(Node::join(middle, pivot, right)))
+    } else if at >= left_chars + own_chars {
+        let (middle, right) = split_at_char(right.unwrap(), at - left_chars - own_chars);
+        (Some(Node::join(left, pivot, middle)), right)
+    } else {
+        let mut text = pivot.recycle().0.text;
+        let offset = text.char_indices().nth(at - left_chars).unwrap().0;
+        let tail = text.split_off(offset);
+        (Some(Node::join(left, leaf(text), None)), Some(Node::join(None, leaf(tail), right)))
+    }
+}
+
+/// Appends the characters in `start .. end` of the subtree to `out`.
+fn write_range(node: &RopeNode, start: usize, end: usize, out: &mut String) {
+    let left_chars = chars(node.child(false));
+    if start < left_chars {
+        write_range(node.child(false).unwrap(), start, end.min(left_chars), out);
+    }
+    let own_end = left_chars + node.data().summary.chars;
+    if start < own_end && left_chars < end {
+        let from = start.max(left_chars) - left_chars;
+        let to = end.min(own_end) - left_chars;
+        out.extend(node.data().text.chars().skip(from).take(to - from));
+    }
+    if own_end < end {
+        write_range(node.child(true).unwrap(), start.max(own_end) - own_end, end - own_end, out);
+    }
+}
+
+fn pop_first(tree: Tree) -> (Option<String>, Tree) {
+    match tree {
+        Some(node) => {
+            let (first, rest) = node.split_at(1);
+            (first.map(|node| node.recycle().0.text), rest)
+        }
+        None => (None, None),
+    }
+}
+
+fn pop_last(tree: Tree) -> (Tree, Option<String>) {
+    match tree {
+        Some(node) => {
+            let count = node.len();
+            let (rest, last) = node.split_at(count - 1);
+            (rest, last.map(|node| node.recycle().0.text))
+        }
+        None => (None, None),
+    }
+}
+
+/// A text buffer that stores UTF-8 chunks in a `Node` tree, which keeps the `TextSummary` of every subtree.
+/// Positions are character indices unless stated otherwise.
+#[derive(Default)]
+pub struct Rope {
+    root: Tree,
+}
+
+impl Rope {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+        }
+    }
+
+    pub fn summary(&self) -> TextSummary {
+        self.root.as_ref().map(|node| *node.sum()).unwrap_or_default()
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.summary().bytes
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.summary().chars
+    }
+
+    pub fn len_utf16(&self) -> usize {
+        self.summary().utf16
+    }
+
+    /// Returns the number of lines, i.e. the number of `'\n'` plus one.
+    pub fn len_lines(&self) -> usize {
+        self.summary().lines + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Inserts `text` before the `char_idx`-th character.
+    /// # Panics
+    /// Panics if `char_idx` is greater than the number of characters.
+    /// # Complexity
+    /// O(log(len) + text.len())
+    pub fn insert_str(&mut self, char_idx: usize, text: &str) {
+        assert!(char_idx <= self.len_chars());
+        if text.is_empty() {
+            return;
+        }
+        let (left, right) = self.split(char_idx);
+        let (left, last) = pop_last(left);
+        let (first, right) = pop_first(right);
+        let mut middle = last.unwrap_or_default();
+        middle.push_str(text);
+        middle.push_str(&first.unwrap_or_default());
+        self.root = Node::merge(Node::merge(left, build(&middle)), right);
+    }
+
+    pub fn insert_char(&mut self, char_idx: usize, c: char) {
+        self.insert_str(char_idx, c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Removes the characters in `char_range`.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(len) + k), where k is the number of chunks in `char_range`, which are dropped
+    pub fn remove<R: RangeBounds<usize>>(&mut self, char_range: R) {
+        let (start, end) = bounds(char_range, self.len_chars());
+        let (left, right) = self.split(end);
+        self.root = left;
+        let (left, _) = self.split(start);
+        let (left, last) = pop_last(left);
+        let (first, right) = pop_first(right);
+        let middle = match (last, first) {
+            (Some(mut last), Some(first)) if last.len() + first.len() <= CHUNK => {
+                last.push_str(&first);
+                build(&last)
+            }
+            (last, first) => Node::merge(last.map(leaf), first.map(leaf)),
+        };
+        self.root = Node::merge(Node::merge(left, middle), right);
+    }
+
+    /// Returns the characters in `char_range` as a `String`.
+    /// # Complexity
+    /// O(log(len) + char_range.len())
+    pub fn slice<R: RangeBounds<usize>>(&self, char_range: R) -> String {
+        let (start, end) = bounds(char_range, self.len_chars());
+        let mut out = String::new();
+        if let Some(root) = &self.root {
+            if start < end {
+                write_range(root, start, end, &mut out);
+            }
+        }
+        out
+    }
+
+    /// Returns the `line_idx`-th line, including its trailing `'\n'` if any.
+    /// # Panics
+    /// Panics if `line_idx` is not less than `len_lines()`.
+    pub fn line(&self, line_idx: usize) -> String {
+        let start = self.line_to_char(line_idx);
+        let end = if line_idx + 1 < self.len_lines() {
+            self.line_to_char(line_idx + 1)
+        } else {
+            self.len_chars()
+        };
+        self.slice(start .. end)
+    }
+
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        self.char_to(char_idx, |s| s.bytes)
+    }
+
+    pub fn char_to_utf16(&self, char_idx: usize) -> usize {
+        self.char_to(char_idx, |s| s.utf16)
+    }
+
+    /// Returns the index of the line containing the `char_idx`-th character.
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        self.char_to(char_idx, |s| s.lines)
+    }
+
+    /// Returns the index of the character containing the `byte_idx`-th byte.
+    pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+        self.to_char(byte_idx, |s| s.bytes)
+    }
+
+    /// Returns the index of the character containing the `utf16_idx`-th UTF-16 code unit.
+    pub fn utf16_to_char(&self, utf16_idx: usize) -> usize {
+        self.to_char(utf16_idx, |s| s.utf16)
+    }
+
+    /// Returns the index of the first character of the `line_idx`-th line.
+    /// # Panics
+    /// Panics if `line_idx` is not less than `len_lines()`.
+    pub fn line_to_char(&self, line_idx: usize) -> usize {
+        assert!(line_idx < self.len_lines());
+        if line_idx == 0 {
+            0
+        } else {
+            self.to_char(line_idx - 1, |s| s.lines) + 1
+        }
+    }
+
+    fn split(&mut self, char_idx: usize) -> (Tree, Tree) {
+        match self.root.take() {
+            Some(root) => split_at_char(root, char_idx),
+            None => (None, None),
+        }
+    }
+
+    /// Finds the first chunk for which `predicate` holds on the summary of the text up to and including it.
+    /// Returns the summary of the text before that chunk, and the chunk.
+    fn locate<P: Fn(&TextSummary) -> bool>(&self, predicate: P) -> Option<(TextSummary, &str)> {
+        let mut node = self.root.as_deref()?;
+        let mut prefix = TextSummary::default();
+        loop {
+            if let Some(left) = node.child(false) {
+                let with_left = prefix + *left.sum();
+                if predicate(&with_left) {
+                    node = left;
+                    continue;
+                }
+                prefix = with_left;
+            }
+            let with_own = prefix + node.data().summary;
+            if predicate(&with_own) {
+                return Some((prefix, &node.data().text));
+            }
+            prefix = with_own;
+            node = node.child(true)?;
+        }
+    }
+
+    /// Converts a character index into the metric selected by `key`.
+    fn char_to<K: Fn(&TextSummary) -> usize>(&self, char_idx: usize, key: K) -> usize {
+        assert!(char_idx <= self.len_chars());
+        match self.locate(|s| s.chars > char_idx) {
+            Some((prefix, chunk)) => {
+                let offset = chunk.char_indices().nth(char_idx - prefix.chars).unwrap().0;
+                key(&(prefix + TextSummary::of(&chunk[.. offset])))
+            }
+            None => key(&self.summary()),
+        }
+    }
+
+    /// Converts an index in the metric selected by `key` into the index of the character containing it.
+    fn to_char<K: Fn(&TextSummary) -> usize>(&self, target: usize, key: K) -> usize {
+        match self.locate(|s| key(s) > target) {
+            Some((mut prefix, chunk)) => {
+                for c in chunk.chars() {
+                    let next = prefix + TextSummary::of_char(c);
+                    if key(&next) > target {
+                        break;
+                    }
+                    prefix = next;
+                }
+                prefix.chars
+            }
+            None => {
+                assert!(target <= key(&self.summary()));
+                self.len_chars()
+            }
+        }
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(text: &str) -> Self {
+        Self {
+            root: build(text),
+        }
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in Iter::new(self.root.as_deref()) {
+            f.write_str(&chunk.text)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Rope ")?;
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rope, RopeNode};
+
+    fn depth(node: Option<&RopeNode>) -> usize {
+        node.map(|node| 1 + depth(node.child(false)).max(depth(node.child(true)))).unwrap_or(0)
+    }
+
+    #[test]
+    fn height() {
+        let mut rope = Rope::new();
+        for i in 0 .. 100000 {
+            rope.insert_str(0, if i % 3 == 0 { "ab\n" } else { "c" });
+        }
+        let root = rope.root.as_deref().unwrap();
+        assert!(depth(Some(root)) as f64 <= 1.44 * (root.len() as f64).log2() + 2.0);
+        assert_eq!(rope.len_lines(), 33335);
+        rope.remove(.. 50000);
+        let root = rope.root.as_deref().unwrap();
+        assert!(depth(Some(root)) as f64 <= 1.44 * (root.len() as f64).log2() + 2.0);
+    }
+
+    #[test]
+    fn edit() {
+        let mut rope = Rope::new();
+        let mut expected = String::new();
+        for i in 0 .. 500 {
+            let text = format!("{}あ\n", i);
+            let at = (i * 7) % (expected.chars().count() + 1);
+            let byte = expected.char_indices().nth(at).map(|(b, _)| b).unwrap_or(expected.len());
+            expected.insert_str(byte, &text);
+            rope.insert_str(at, &text);
+        }
+        assert_eq!(rope.to_string(), expected);
+        rope.remove(100 .. 1500);
+        let chars = expected.chars().collect::<Vec<_>>();
+        let expected = chars[.. 100].iter().chain(&chars[1500 ..]).collect::<String>();
+        assert_eq!(rope.to_string(), expected);
+        assert_eq!(rope.len_chars(), expected.chars().count());
+        assert_eq!(rope.len_bytes(), expected.len());
+        assert_eq!(rope.len_utf16(), expected.encode_utf16().count());
+    }
+
+    #[test]
+    #[should_panic(expected = "range end overflows")]
+    fn range_overflow() {
+        Rope::from("abc").slice(1 ..= usize::MAX);
+    }
+
+    #[test]
+    fn lines() {
+        let rope = Rope::from("abc\nあいう\n\n😀x");
+        assert_eq!(rope.len_lines(), 4);
+        assert_eq!(rope.line(0), "abc\n");
+        assert_eq!(rope.line(1), "あいう\n");
+        assert_eq!(rope.line(2), "\n");
+        assert_eq!(rope.line(3), "😀x");
+        assert_eq!(rope.line_to_char(3), 9);
+        assert_eq!(rope.char_to_line(3), 0);
+        assert_eq!(rope.char_to_line(4), 1);
+        assert_eq!(rope.char_to_line(10), 3);
+        assert_eq!(rope.char_to_byte(5), 7);
+        assert_eq!(rope.byte_to_char(7), 5);
+        assert_eq!(rope.char_to_utf16(10), 11);
+        assert_eq!(rope.utf16_to_char(11), 10);
+    }
+}
@@ -0,0 +1,251 @@
+//! Join-based set operations on sorted lists.
+//! Each step exposes the root of the shorter tree, splits the longer one around it and joins the results
+//! with `Node::join`, which costs O(difference of heights). Summed over the recursion this is
+//! O(m log(n / m + 1)) for lengths m <= n with `Avl` and `WeightBalanced`, and expected so with `Treap`.
+
+use super::*;
+
+/// Splits `tree` into the elements less than `x`, one element equal to `x` if any, and the rest.
+fn split_three<T: Ord, B: Balance>(tree: Tree<T, B>, x: &T) -> (Tree<T, B>, Tree<T, B>, Tree<T, B>) {
+    let root = match tree {
+        Some(root) => root,
+        None => return (None, None, None),
+    };
+    let at = root.leftmost(|y| y.data() >= x).unwrap_or(root.len());
+    match root.split_at(at) {
+        (less, Some(rest)) if rest.at(0).data() == x => {
+            let (equal, greater) = rest.split_at(1);
+            (less, equal, greater)
+        }
+        (less, rest) => (less, None, rest),
+    }
+}
+
+fn join<T, B: Balance>(left: Tree<T, B>, middle: PinnedNode<T, B>, right: Tree<T, B>) -> Tree<T, B> {
+    Some(Node::join(left, middle, right))
+}
+
+fn union<T: Ord, B: Balance>(a: Tree<T, B>, b: Tree<T, B>) -> Tree<T, B> {
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(a), b) => {
+            let (left, middle, right) = a.detach();
+            let (b_left, _, b_right) = split_three(b, middle.data());
+            join(union(left, b_left), middle, union(right, b_right))
+        }
+    }
+}
+
+fn intersection<T: Ord, B: Balance>(a: Tree<T, B>, b: Tree<T, B>) -> Tree<T, B> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let (left, middle, right) = a.detach();
+            let (b_left, equal, b_right) = split_three(Some(b), middle.data());
+            let left = intersection(left, b_left);
+            let right = intersection(right, b_right);
+            if equal.is_some() {
+                join(left, middle, right)
+            } else {
+                Node::merge(left, right)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn difference<T: Ord, B: Balance>(a: Tree<T, B>, b: Tree<T, B>) -> Tree<T, B> {
+    match (a, b) {
+        (None, _) => None,
+        (a, None) => a,
+        (Some(a), Some(b)) if a.len() <= b.len() => {
+            let (left, middle, right) = a.detach();
+            let (b_left, equal, b_right) = split_three(Some(b), middle.data());
+            let left = difference(left, b_left);
+            let right = difference(right, b_right);
+            if equal.is_some() {
+                Node::merge(left, right)
+            } else {
+                join(left, middle, right)
+            }
+        }
+        (Some(a), Some(b)) => {
+            let (b_left, middle, b_right) = b.detach();
+            let (left, _, right) = split_three(Some(a), middle.data());
+            Node::merge(difference(left, b_left), difference(right, b_right))
+        }
+    }
+}
+
+fn symmetric_difference<T: Ord, B: Balance>(a: Tree<T, B>, b: Tree<T, B>) -> Tree<T, B> {
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(a), b) => {
+            let (left, middle, right) = a.detach();
+            let (b_left, equal, b_right) = split_three(b, middle.data());
+            let left = symmetric_difference(left, b_left);
+            let right = symmetric_difference(right, b_right);
+            if equal.is_some() {
+                Node::merge(left, right)
+            } else {
+                join(left, middle, right)
+            }
+        }
+    }
+}
+
+/// Merges keeping duplicates; equal elements of `a` come before those of `b`.
+fn merge_sorted<T: Ord, B: Balance>(a: Tree<T, B>, b: Tree<T, B>) -> Tree<T, B> {
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(a), Some(b)) if a.len() <= b.len() => {
+            let (left, middle, right) = a.detach();
+            let at = b.leftmost(|y| y.data() >= middle.data()).unwrap_or(b.len());
+            let (b_left, b_right) = b.split_at(at);
+            join(merge_sorted(left, b_left), middle, merge_sorted(right, b_right))
+        }
+        (Some(a), Some(b)) => {
+            let (b_left, middle, b_right) = b.detach();
+            let at = a.leftmost(|y| y.data() > middle.data()).unwrap_or(a.len());
+            let (left, right) = a.split_at(at);
+            join(merge_sorted(left, b_left), middle, merge_sorted(right, b_right))
+        }
+    }
+}
+
+/// Orders `a` and `b` so that the first one is the shorter.
+fn by_len<T, B: Balance>(mut a: TreeList<T, B>, mut b: TreeList<T, B>) -> (Tree<T, B>, Tree<T, B>) {
+    if a.len() <= b.len() {
+        (a.root.take(), b.root.take())
+    } else {
+        (b.root.take(), a.root.take())
+    }
+}
+
+/// The operations below assume both lists are sorted.
+/// The set operations additionally assume that neither list contains duplicates.
+/// Each costs O(m log(n / m + 1)), where m and n are the shorter and the longer length.
+impl<T: Ord, B: Balance> TreeList<T, B> {
+    /// Returns the elements in `self` or `other`.
+    /// When both contain an element, the one from the shorter list is kept.
+    pub fn union(self, other: Self) -> Self {
+        let (a, b) = by_len(self, other);
+        Self::raw(union(a, b))
+    }
+
+    /// Returns the elements in both `self` and `other`.
+    /// The element from the shorter list is kept.
+    pub fn intersection(self, other: Self) -> Self {
+        let (a, b) = by_len(self, other);
+        Self::raw(intersection(a, b))
+    }
+
+    /// Returns the elements in `self` but not in `other`.
+    pub fn difference(mut self, mut other: Self) -> Self {
+        Self::raw(difference(self.root.take(), other.root.take()))
+    }
+
+    /// Returns the elements in exactly one of `self` and `other`.
+    pub fn symmetric_difference(self, other: Self) -> Self {
+        let (a, b) = by_len(self, other);
+        Self::raw(symmetric_difference(a, b))
+    }
+
+    /// Merges two sorted lists into one sorted list, keeping duplicates.
+    /// The merge is stable: equal elements of `self` come before those of `other`.
+    pub fn merge_sorted(mut self, mut other: Self) -> Self {
+        Self::raw(merge_sorted(self.root.take(), other.root.take()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TreeList;
+
+    fn list(v: &[i32]) -> TreeList<i32> {
+        v.iter().copied().collect()
+    }
+
+    fn vec(list: TreeList<i32>) -> Vec<i32> {
+        list.iter().copied().collect()
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = [1, 3, 4, 7, 9, 10, 15];
+        let b = [2, 3, 7, 8, 10, 20, 21, 22, 23];
+        assert_eq!(vec(list(&a).union(list(&b))), vec![1, 2, 3, 4, 7, 8, 9, 10, 15, 20, 21, 22, 23]);
+        assert_eq!(vec(list(&a).intersection(list(&b))), vec![3, 7, 10]);
+        assert_eq!(vec(list(&a).difference(list(&b))), vec![1, 4, 9, 15]);
+        assert_eq!(vec(list(&b).difference(list(&a))), vec![2, 8, 20, 21, 22, 23]);
+        assert_eq!(vec(list(&a).symmetric_difference(list(&b))), vec![1, 2, 4, 8, 9, 15, 20, 21, 22, 23]);
+        assert_eq!(vec(list(&a).union(list(&[]))), a.to_vec());
+        assert_eq!(vec(list(&[]).intersection(list(&b))), vec![]);
+    }
+
+    /// Ordered by the key only, so the tag shows which list an element came from.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct Keyed(i32, u8);
+
+    impl PartialOrd for Keyed {
+        fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Keyed {
+        fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    #[test]
+    fn merge_sorted() {
+        let mut a = (0 .. 100).map(|i| Keyed(i * 3 % 50, 0)).collect::<Vec<_>>();
+        let mut b = (0 .. 70).map(|i| Keyed(i * 7 % 40, 1)).collect::<Vec<_>>();
+        a.sort();
+        b.sort();
+        for (a, b) in [(&a, &b), (&b, &a)] {
+            let mut expected = a.iter().chain(b).copied().collect::<Vec<_>>();
+            expected.sort();
+            let merged = a.iter().copied().collect::<TreeList<_>>().merge_sorted(b.iter().copied().collect());
+            assert_eq!(merged.iter().copied().collect::<Vec<_>>(), expected);
+        }
+    }
+
+    /// Compares with `BTreeSet` for operands of very different lengths, in both orders and with every strategy.
+    fn against_btree_set<B: crate::balance::Balance>() {
+        use std::collections::BTreeSet;
+        let mut seed = 3u64;
+        let mut next = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+        for &(m, n) in &[(0, 50), (3, 5000), (100, 100), (700, 20000)] {
+            let a = (0 .. m).map(|_| next(3 * n as u64 + 1)).collect::<BTreeSet<_>>();
+            let b = (0 .. n).map(|_| next(3 * n as u64 + 1)).collect::<BTreeSet<_>>();
+            let list = |s: &BTreeSet<u64>| s.iter().copied().collect::<TreeList<_, B>>();
+            let vec = |l: TreeList<u64, B>| l.iter().copied().collect::<Vec<_>>();
+            for (a, b) in [(&a, &b), (&b, &a)] {
+                assert_eq!(vec(list(a).union(list(b))), a.union(b).copied().collect::<Vec<_>>());
+                assert_eq!(vec(list(a).intersection(list(b))), a.intersection(b).copied().collect::<Vec<_>>());
+                assert_eq!(vec(list(a).difference(list(b))), a.difference(b).copied().collect::<Vec<_>>());
+                assert_eq!(vec(list(a).symmetric_difference(list(b))), a.symmetric_difference(b).copied().collect::<Vec<_>>());
+                let mut merged = a.iter().chain(b).copied().collect::<Vec<_>>();
+                merged.sort();
+                assert_eq!(vec(list(a).merge_sorted(list(b))), merged);
+            }
+        }
+    }
+
+    #[test]
+    fn random_sets() {
+        use crate::balance::*;
+        against_btree_set::<Avl>();
+        against_btree_set::<Treap>();
+        against_btree_set::<WeightBalanced>();
+        against_btree_set::<Splay>();
+    }
+}
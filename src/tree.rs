@@ -0,0 +1,196 @@
+//! Searches, rotations, joins and splits of binary trees ordered by position, written once for
+//! `Node` and `PersistentNode`. The node types only say how to reach and replace their children.
+
+/// A node of a binary tree that knows the length of its subtree.
+pub(crate) trait BinaryNode {
+    fn len(&self) -> usize;
+
+    fn child(&self, dir: bool) -> Option<&Self>;
+
+    fn child_len(&self, dir: bool) -> usize {
+        self.child(dir).map(|child| child.len()).unwrap_or(0)
+    }
+}
+
+/// An owning pointer to a node, through which the tree is restructured.
+pub(crate) trait Link: Sized {
+    type Node: BinaryNode;
+
+    fn node(&self) -> &Self::Node;
+
+    fn replace_child(&mut self, dir: bool, replacement: Option<Self>) -> Option<Self>;
+
+    /// Recomputes the length and whatever else the node keeps from its children.
+    fn update(&mut self);
+
+    /// Returns the side that has to shrink for the two trees to be joined under one node, if any.
+    fn heavy_side(left: Option<&Self::Node>, right: Option<&Self::Node>) -> Option<bool>;
+
+    /// Restores the invariant of the node after one of its children changed.
+    fn balance(self) -> Self;
+
+    /// Joins `left`, `pivot` and `right` in this order, where `pivot` is a single node.
+    fn join(left: Option<Self>, pivot: Self, right: Option<Self>) -> Self;
+}
+
+pub(crate) fn at<N: BinaryNode>(mut node: &N, mut index: usize) -> &N {
+    loop {
+        let left_len = node.child_len(false);
+        if index < left_len {
+            node = node.child(false).unwrap();
+        } else if index == left_len {
+            return node;
+        } else {
+            index -= left_len + 1;
+            node = node.child(true).unwrap();
+        }
+    }
+}
+
+/// Returns the first position whose node satisfies `predicate`,
+/// assuming that the nodes satisfying it form a suffix.
+pub(crate) fn leftmost<N: BinaryNode, P: Fn(&N) -> bool>(mut node: &N, predicate: P) -> Option<usize> {
+    let mut offset = 0;
+    let mut result = None;
+    loop {
+        let left_len = node.child_len(false);
+        let next = if predicate(node) {
+            result = Some(offset + left_len);
+            node.child(false)
+        } else {
+            offset += left_len + 1;
+            node.child(true)
+        };
+        match next {
+            Some(next) => node = next,
+            None => return result,
+        }
+    }
+}
+
+/// Returns the last position whose node satisfies `predicate`,
+/// assuming that the nodes satisfying it form a prefix.
+pub(crate) fn rightmost<N: BinaryNode, P: Fn(&N) -> bool>(mut node: &N, predicate: P) -> Option<usize> {
+    let mut offset = 0;
+    let mut result = None;
+    loop {
+        let left_len = node.child_len(false);
+        let next = if predicate(node) {
+            result = Some(offset + left_len);
+            offset += left_len + 1;
+            node.child(true)
+        } else {
+            node.child(false)
+        };
+        match next {
+            Some(next) => node = next,
+            None => return result,
+        }
+    }
+}
+
+/// Moves the child on side `!dir` up, and `node` down to side `dir`.
+pub(crate) fn rotate<L: Link>(mut node: L, dir: bool) -> L {
+    match node.replace_child(!dir, None) {
+        Some(mut child) => {
+            let inner = child.replace_child(dir, None);
+            node.replace_child(!dir, inner);
+            node.update();
+            child.replace_child(dir, Some(node));
+            child.update();
+            child
+        }
+        None => node,
+    }
+}
+
+/// Takes both children, returning `(left, node, right)` with `node` as a single node.
+pub(crate) fn detach<L: Link>(mut node: L) -> (Option<L>, L, Option<L>) {
+    let left = node.replace_child(false, None);
+    let right = node.replace_child(true, None);
+    node.update();
+    (left, node, right)
+}
+
+/// Joins `left`, `pivot` and `right` in this order, where `pivot` is a single node.
+/// Descends the spine of the heavier side until `L::heavy_side` considers both sides balanced,
+/// so it costs O(difference of heights).
+pub(crate) fn join_spine<L: Link>(left: Option<L>, mut pivot: L, right: Option<L>) -> L {
+    let heavy = L::heavy_side(left.as_ref().map(L::node), right.as_ref().map(L::node));
+    match heavy {
+        Some(false) => {
+            let mut left = left.unwrap();
+            let inner = left.replace_child(true, None);
+            left.replace_child(true, Some(join_spine(inner, pivot, right)));
+            left.update();
+            left.balance()
+        }
+        Some(true) => {
+            let mut right = right.unwrap();
+            let inner = right.replace_child(false, None);
+            right.replace_child(false, Some(join_spine(left, pivot, inner)));
+            right.update();
+            right.balance()
+        }
+        None => {
+            pivot.replace_child(false, left);
+            pivot.replace_child(true, right);
+            pivot.update();
+            pivot
+        }
+    }
+}
+
+/// Splits by descending to the split point and joining the pieces on the way back up.
+pub(crate) fn split_at<L: Link>(node: L, at: usize) -> (Option<L>, Option<L>) {
+    let len = node.node().len();
+    assert!(at <= len);
+    if at == 0 {
+        return (None, Some(node));
+    } else if at == len {
+        return (Some(node), None);
+    }
+    let left_len = node.node().child_len(false);
+    let (left, pivot, right) = detach(node);
+    if at <= left_len {
+        let (left, middle) = split_at(left.unwrap(), at);
+        (left, Some(L::join(middle, pivot, right)))
+    } else {
+        let (middle, right) = split_at(right.unwrap(), at - left_len - 1);
+        (Some(L::join(left, pivot, middle)), right)
+    }
+}
+
+/// Concatenates by joining with the first element of `right` as the pivot.
+pub(crate) fn append<L: Link>(left: L, right: L) -> L {
+    let (first, rest) = split_at(right, 1);
+    L::join(Some(left), first.unwrap(), rest)
+}
+
+/// `heavy_side` of an AVL tree whose nodes keep their height.
+pub(crate) fn avl_heavy_side<N>(left: Option<&N>, right: Option<&N>, height: impl Fn(Option<&N>) -> u8) -> Option<bool> {
+    let lh = height(left);
+    let rh = height(right);
+    if lh + 1 < rh {
+        Some(true)
+    } else if rh + 1 < lh {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// `balance` of an AVL tree: a single rotation, or a double one if the inner grandchild is the taller.
+pub(crate) fn avl_balance<L: Link>(mut node: L, height: impl Fn(Option<&L::Node>) -> u8) -> L {
+    let dir = match avl_heavy_side(node.node().child(false), node.node().child(true), &height) {
+        Some(dir) => dir,
+        None => return node,
+    };
+    let child = node.node().child(dir).unwrap();
+    if height(child.child(!dir)) > height(child.child(dir)) {
+        let child = rotate(node.replace_child(dir, None).unwrap(), dir);
+        node.replace_child(dir, Some(child));
+        node.update();
+    }
+    rotate(node, !dir)
+}
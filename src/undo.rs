@@ -0,0 +1,429 @@
+//! `TreeList` wrapper that records the inverse of every edit for undo and redo.
+//!
+//! Every edit is recorded as another edit that restores the previous state, and applying an inverse
+//! returns the inverse of that inverse, so the same records serve both undo and redo.
+
+use core::ops::*;
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use super::{bounds, balance::{Balance, Avl}, TreeList};
+
+enum Op<T, B: Balance> {
+    /// Replaces `len` elements starting at `index` with `with`.
+    Replace { index: usize, len: usize, with: TreeList<T, B> },
+    /// Replaces the `index`-th element with `value`.
+    Set { index: usize, value: T },
+    Reverse,
+}
+
+impl<T, B: Balance> Op<T, B> {
+    /// Applies the edit in O(log(len)) and returns its inverse.
+    fn apply(self, list: &mut TreeList<T, B>) -> Self {
+        match self {
+            Op::Replace { index, len, mut with } => {
+                let mut removed = list.split_off(index);
+                let mut rest = removed.split_off(len);
+                let inserted = with.len();
+                list.append(&mut with);
+                list.append(&mut rest);
+                Op::Replace { index, len: inserted, with: removed }
+            }
+            Op::Set { index, value } => {
+                let value = core::mem::replace(&mut list[index], value);
+                Op::Set { index, value }
+            }
+            Op::Reverse => {
+                list.reverse();
+                Op::Reverse
+            }
+        }
+    }
+}
+
+/// Applies the edits of a group from the last one and returns the group that cancels it.
+/// A group is stored in the reverse order of application, which keeps undo and redo symmetric.
+fn apply_group<T, B: Balance>(list: &mut TreeList<T, B>, group: Vec<Op<T, B>>) -> Vec<Op<T, B>> {
+    group.into_iter().rev().map(|op| op.apply(list)).collect()
+}
+
+/// A `TreeList` whose edits can be undone and redone.
+/// Edits made inside a transaction are undone together; other edits are undone one by one.
+/// Reading goes through `Deref<Target = TreeList<T, B>>`.
+pub struct UndoableTreeList<T, B: Balance = Avl> {
+    list: TreeList<T, B>,
+    undo_stack: VecDeque<Vec<Op<T, B>>>,
+    redo_stack: Vec<Vec<Op<T, B>>>,
+    transaction: Option<Vec<Op<T, B>>>,
+    history_limit: usize,
+}
+
+impl<T: Clone> UndoableTreeList<T> {
+    pub fn new() -> Self {
+        Self::from_list(TreeList::new())
+    }
+}
+
+impl<T: Clone, B: Balance> UndoableTreeList<T, B> {
+    /// Wraps `list` with an empty history.
+    pub fn from_list(list: TreeList<T, B>) -> Self {
+        Self { list, undo_stack: VecDeque::new(), redo_stack: vec![], transaction: None, history_limit: usize::MAX }
+    }
+
+    /// Limits the number of edits or transactions that can be undone; older ones are forgotten.
+    /// # Panics
+    /// Panics if limit is 0, since the last entry holds what `splice` and `split_off` return.
+    pub fn with_history_limit(mut self, limit: usize) -> Self {
+        self.set_history_limit(limit);
+        self
+    }
+
+    /// # Panics
+    /// Panics if limit is 0.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        assert!(limit > 0, "the history limit must be positive");
+        self.history_limit = limit;
+        self.trim_history();
+    }
+
+    pub fn into_inner(self) -> TreeList<T, B> {
+        self.list
+    }
+
+    fn trim_history(&mut self) {
+        while self.undo_stack.len() > self.history_limit {
+            self.undo_stack.pop_front();
+        }
+    }
+
+    /// Adds `op` to the open transaction, or to the history as an entry of its own, and returns it.
+    fn record(&mut self, op: Op<T, B>) -> &Op<T, B> {
+        if self.transaction.is_none() {
+            self.push_history(vec![]);
+        }
+        let group = match &mut self.transaction {
+            Some(ops) => ops,
+            None => self.undo_stack.back_mut().unwrap(),
+        };
+        group.push(op);
+        group.last().unwrap()
+    }
+
+    /// Records that `with` was replaced by `len` elements at `index`, and returns `with` from the history.
+    fn record_replace(&mut self, index: usize, len: usize, with: TreeList<T, B>) -> &TreeList<T, B> {
+        match self.record(Op::Replace { index, len, with }) {
+            Op::Replace { with, .. } => with,
+            _ => unreachable!(),
+        }
+    }
+
+    fn push_history(&mut self, group: Vec<Op<T, B>>) {
+        self.redo_stack.clear();
+        self.undo_stack.push_back(group);
+        self.trim_history();
+    }
+
+    /// Starts grouping the following edits until `commit` or `rollback`.
+    /// # Panics
+    /// Panics if a transaction is already open.
+    pub fn begin_transaction(&mut self) {
+        assert!(self.transaction.is_none(), "a transaction is already open");
+        self.transaction = Some(vec![]);
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Closes the transaction, recording its edits as one entry of the history.
+    /// # Panics
+    /// Panics if no transaction is open.
+    pub fn commit(&mut self) {
+        let group = self.transaction.take().expect("no transaction is open");
+        if !group.is_empty() {
+            self.push_history(group);
+        }
+    }
+
+    /// Closes the transaction and reverts its edits without recording them.
+    /// # Panics
+    /// Panics if no transaction is open.
+    pub fn rollback(&mut self) {
+        let group = self.transaction.take().expect("no transaction is open");
+        apply_group(&mut self.list, group);
+    }
+
+    /// Reverts the last edit or transaction. Returns `false` if there is nothing to undo.
+    /// # Panics
+    /// Panics if a transaction is open.
+    pub fn undo(&mut self) -> bool {
+        assert!(self.transaction.is_none(), "cannot undo inside a transaction");
+        match self.undo_stack.pop_back() {
+            Some(group) => {
+                let group = apply_group(&mut self.list, group);
+                self.redo_stack.push(group);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reapplies the last undone edit or transaction. Returns `false` if there is nothing to redo.
+    /// # Panics
+    /// Panics if a transaction is open.
+    pub fn redo(&mut self) -> bool {
+        assert!(self.transaction.is_none(), "cannot redo inside a transaction");
+        match self.redo_stack.pop() {
+            Some(group) => {
+                let group = apply_group(&mut self.list, group);
+                self.undo_stack.push_back(group);
+                self.trim_history();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Forgets all the history.
+    pub fn clear_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn insert(&mut self, index: usize, element: T) {
+        self.list.insert(index, element);
+        self.record(Op::Replace { index, len: 1, with: TreeList::default() });
+    }
+
+    pub fn push_front(&mut self, element: T) {
+        self.insert(0, element);
+    }
+
+    pub fn push_back(&mut self, element: T) {
+        self.insert(self.len(), element);
+    }
+
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let element = self.list.remove(index)?;
+        let mut with = TreeList::default();
+        with.push_back(element.clone());
+        self.record(Op::Replace { index, len: 0, with });
+        Some(element)
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() { None } else { self.remove(0) }
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.is_empty() { None } else { self.remove(self.len() - 1) }
+    }
+
+    /// Replaces `range` with `replace_with` and returns the removed elements,
+    /// which are moved into the history rather than cloned.
+    /// # Panics
+    /// Panics if the range is out of bounds.
+    /// # Complexity
+    /// O(log(len) + m log(len)) where m is the number of inserted elements
+    pub fn splice<R, I>(&mut self, range: R, replace_with: I) -> &TreeList<T, B>
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = T>,
+    {
+        let (l, r) = bounds(range, self.len());
+        let len = self.len();
+        let removed = self.list.splice(l .. r, replace_with);
+        let inserted = self.len() + (r - l) - len;
+        self.record_replace(l, inserted, removed)
+    }
+
+    /// Moves all the elements of `other` into `self`, leaving `other` empty.
+    /// # Complexity
+    /// O(log(len + other.len))
+    pub fn append(&mut self, other: &mut TreeList<T, B>) {
+        let index = self.len();
+        let len = other.len();
+        self.list.append(other);
+        self.record(Op::Replace { index, len, with: TreeList::default() });
+    }
+
+    /// Splits the list into two at the given index. Returns the second list,
+    /// which is moved into the history rather than cloned.
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn split_off(&mut self, at: usize) -> &TreeList<T, B> {
+        let tail = self.list.split_off(at);
+        self.record_replace(at, 0, tail)
+    }
+
+    /// # Complexity
+    /// O(1); the elements are moved into the history.
+    pub fn clear(&mut self) {
+        let len = self.len();
+        let all = self.list.split_off(0);
+        if len > 0 {
+            self.record_replace(0, 0, all);
+        }
+    }
+
+    /// Replaces the `index`-th element with `value`.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    /// # Complexity
+    /// O(log(len))
+    pub fn set(&mut self, index: usize, value: T) {
+        let value = core::mem::replace(&mut self.list[index], value);
+        self.record(Op::Set { index, value });
+    }
+
+    /// # Complexity
+    /// O(len)
+    pub fn reverse(&mut self) {
+        self.list.reverse();
+        self.record(Op::Reverse);
+    }
+}
+
+impl<T: Clone, B: Balance> Default for UndoableTreeList<T, B> {
+    fn default() -> Self {
+        Self::from_list(TreeList::default())
+    }
+}
+
+impl<T, B: Balance> Deref for UndoableTreeList<T, B> {
+    type Target = TreeList<T, B>;
+
+    fn deref(&self) -> &TreeList<T, B> {
+        &self.list
+    }
+}
+
+impl<T, B: Balance> Index<usize> for UndoableTreeList<T, B> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.list[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(list: &UndoableTreeList<u32>) -> Vec<u32> {
+        list.iter().copied().collect()
+    }
+
+    #[test]
+    fn random_undo_redo() {
+        let mut list = UndoableTreeList::new();
+        let mut states = vec![vec![]];
+        let mut current = 0usize;
+        let mut seed = 11u64;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0 .. 3000 {
+            let len = list.len();
+            let (a, b) = (next(len + 1), next(len + 1));
+            match next(10) {
+                0 | 1 => {
+                    assert_eq!(list.undo(), current > 0);
+                    current = current.saturating_sub(1);
+                }
+                2 => {
+                    assert_eq!(list.redo(), current + 1 < states.len());
+                    current = (current + 1).min(states.len() - 1);
+                }
+                op => {
+                    match op {
+                        3 | 4 => list.insert(a, next(100) as u32),
+                        5 if len > 0 => {
+                            list.remove(a.min(len - 1));
+                        }
+                        6 => {
+                            let removed = list.splice(a.min(b) .. a.max(b), (0 .. next(3)).map(|x| x as u32));
+                            assert_eq!(removed.len(), a.max(b) - a.min(b));
+                        }
+                        7 if len > 0 => {
+                            let at = a.min(len - 1);
+                            list.set(at, list[at] + 1);
+                        }
+                        8 => {
+                            list.begin_transaction();
+                            let mut tail = list.split_off(a).iter().copied().collect::<TreeList<_>>();
+                            tail.push_back(100);
+                            list.append(&mut tail);
+                            list.commit();
+                        }
+                        _ => list.reverse(),
+                    }
+                    states.truncate(current + 1);
+                    states.push(contents(&list));
+                    current += 1;
+                }
+            }
+            assert_eq!(contents(&list), states[current]);
+        }
+        while list.undo() {}
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn transactions() {
+        let mut list = UndoableTreeList::new().with_history_limit(2);
+        list.push_back(1);
+        list.begin_transaction();
+        list.push_back(2);
+        list.push_back(3);
+        list.rollback();
+        assert_eq!(contents(&list), vec![1]);
+        list.begin_transaction();
+        list.push_back(2);
+        list.set(0, 10);
+        list.reverse();
+        list.commit();
+        assert_eq!(contents(&list), vec![2, 10]);
+        list.clear();
+        assert!(list.undo());
+        assert!(list.undo());
+        assert_eq!(contents(&list), vec![1]);
+        // The first push was dropped from the bounded history.
+        assert!(!list.undo());
+        assert!(list.redo());
+        assert_eq!(contents(&list), vec![2, 10]);
+        list.push_back(4);
+        assert!(!list.redo());
+    }
+
+    #[test]
+    fn removed_elements_are_moved() {
+        use alloc::rc::Rc;
+        let marker = Rc::new(());
+        let mut list = UndoableTreeList::from_list((0 .. 4).map(|_| marker.clone()).collect::<TreeList<_>>());
+        assert_eq!(list.split_off(1).len(), 3);
+        assert_eq!(list.splice(.., None).len(), 1);
+        assert_eq!(Rc::strong_count(&marker), 5);
+        assert!(list.undo() && list.undo());
+        assert_eq!(list.len(), 4);
+        assert_eq!(Rc::strong_count(&marker), 5);
+    }
+}